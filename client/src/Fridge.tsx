@@ -153,8 +153,23 @@ function FoodItem({ name, bestBeforeDate, id }: Food) {
         </Typography>
       </CardContent>
       <CardActions>
-        <Button size="small" onClick={() => deleteFood(id)}>
-          Delete
+        <Button
+          size="small"
+          onClick={() => deleteFood({ foodId: id, reason: "consumed" })}
+        >
+          Eaten
+        </Button>
+        <Button
+          size="small"
+          onClick={() => deleteFood({ foodId: id, reason: "thrownAway" })}
+        >
+          Thrown away
+        </Button>
+        <Button
+          size="small"
+          onClick={() => deleteFood({ foodId: id, reason: "givenAway" })}
+        >
+          Given away
         </Button>
       </CardActions>
     </Card>
@@ -38,6 +38,8 @@ export interface Food {
   bestBeforeDate: Dayjs
 }
 
+export type RemovalReason = "consumed" | "thrownAway" | "givenAway"
+
 export interface NewFood {
   name: string
   bestBeforeDate: Dayjs
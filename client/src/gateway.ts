@@ -10,6 +10,7 @@ import {
   NewFood,
   NewRecipe,
   Recipe,
+  RemovalReason,
 } from "./domain"
 
 export async function logIn({
@@ -223,9 +224,15 @@ export async function createFood({
   }
 }
 
-export async function deleteFood(foodId: number): Promise<void> {
+export async function deleteFood({
+  foodId,
+  reason,
+}: {
+  foodId: number
+  reason: RemovalReason
+}): Promise<void> {
   try {
-    const res = await fetch(`/api/v0/foods/${foodId}`, {
+    const res = await fetch(`/api/v0/foods/${foodId}?reason=${reason}`, {
       method: "DELETE",
     })
     if (!res.ok) throw new Error("Failed to delete food")
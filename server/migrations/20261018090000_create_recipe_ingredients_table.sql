@@ -0,0 +1,12 @@
+CREATE TABLE recipe_ingredients (
+  id SERIAL PRIMARY KEY,
+  recipe_id UUID NOT NULL,
+  position INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  quantity DOUBLE PRECISION,
+  unit TEXT,
+  note TEXT,
+  CONSTRAINT fk_recipe FOREIGN KEY (recipe_id) REFERENCES recipes (id) ON DELETE CASCADE
+);
+
+CREATE INDEX recipe_ingredients_recipe_id_idx ON recipe_ingredients (recipe_id, position);
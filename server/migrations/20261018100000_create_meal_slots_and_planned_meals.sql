@@ -0,0 +1,35 @@
+CREATE TABLE meal_slots (
+  name TEXT PRIMARY KEY,
+  position INTEGER NOT NULL,
+  randomizable BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+INSERT INTO meal_slots (name, position, randomizable)
+VALUES ('lunch', 1, TRUE), ('dinner', 2, TRUE);
+
+CREATE TABLE planned_meals (
+  date DATE NOT NULL,
+  slot TEXT NOT NULL,
+  recipe_id UUID,
+  is_cheat BOOLEAN NOT NULL DEFAULT FALSE,
+  PRIMARY KEY (date, slot),
+  CONSTRAINT fk_slot FOREIGN KEY (slot) REFERENCES meal_slots (name) ON DELETE CASCADE ON UPDATE CASCADE,
+  CONSTRAINT fk_recipe FOREIGN KEY (recipe_id) REFERENCES recipes (id) ON DELETE CASCADE
+);
+
+CREATE INDEX planned_meals_recipe_id_idx ON planned_meals (recipe_id);
+
+INSERT INTO planned_meals (date, slot, recipe_id, is_cheat)
+SELECT date, 'lunch', lunch_id, coalesce(lunch_is_cheat, FALSE)
+FROM days
+WHERE lunch_id IS NOT NULL OR lunch_is_cheat;
+
+INSERT INTO planned_meals (date, slot, recipe_id, is_cheat)
+SELECT date, 'dinner', dinner_id, coalesce(dinner_is_cheat, FALSE)
+FROM days
+WHERE dinner_id IS NOT NULL OR dinner_is_cheat;
+
+-- Kept as a snapshot for one release in case the migration has to be
+-- undone, to be dropped in the next one.
+ALTER TABLE days RENAME TO days_legacy;
+ALTER TABLE days_legacy DROP CONSTRAINT fk_lunch, DROP CONSTRAINT fk_dinner;
@@ -0,0 +1,8 @@
+CREATE TABLE recipe_tags (
+  recipe_id UUID NOT NULL,
+  tag TEXT NOT NULL,
+  PRIMARY KEY (recipe_id, tag),
+  CONSTRAINT fk_recipe FOREIGN KEY (recipe_id) REFERENCES recipes (id) ON DELETE CASCADE
+);
+
+CREATE INDEX recipe_tags_tag_idx ON recipe_tags (tag);
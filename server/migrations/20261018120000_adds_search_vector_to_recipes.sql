@@ -0,0 +1,7 @@
+ALTER TABLE recipes
+ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+  setweight(to_tsvector('english', coalesce(name, '')), 'A') ||
+  setweight(to_tsvector('english', coalesce(body_plain_text, '')), 'B')
+) STORED;
+
+CREATE INDEX recipes_search_vector_idx ON recipes USING GIN (search_vector);
@@ -0,0 +1,25 @@
+CREATE TABLE recipe_revisions (
+  recipe_id UUID NOT NULL,
+  revision INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  name TEXT NOT NULL,
+  quick BOOLEAN NOT NULL,
+  body_html TEXT NOT NULL,
+  body_plain_text TEXT NOT NULL,
+  servings INTEGER,
+  PRIMARY KEY (recipe_id, revision),
+  CONSTRAINT fk_recipe FOREIGN KEY (recipe_id) REFERENCES recipes (id) ON DELETE CASCADE
+);
+
+CREATE TABLE recipe_revision_ingredients (
+  recipe_id UUID NOT NULL,
+  revision INTEGER NOT NULL,
+  position INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  quantity DOUBLE PRECISION,
+  unit TEXT,
+  note TEXT,
+  PRIMARY KEY (recipe_id, revision, position),
+  CONSTRAINT fk_recipe_revision FOREIGN KEY (recipe_id, revision)
+    REFERENCES recipe_revisions (recipe_id, revision) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE recipes ADD COLUMN archived_at TIMESTAMPTZ;
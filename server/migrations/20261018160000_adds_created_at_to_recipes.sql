@@ -0,0 +1,12 @@
+ALTER TABLE recipes ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Older recipes existed at least since they were first planned.
+UPDATE recipes r
+SET created_at = first_planned.date
+FROM (
+  SELECT recipe_id, min(date)::timestamptz AS date
+  FROM planned_meals
+  WHERE recipe_id IS NOT NULL
+  GROUP BY recipe_id
+) first_planned
+WHERE first_planned.recipe_id = r.id;
@@ -0,0 +1 @@
+ALTER TABLE planned_meals ADD COLUMN locked BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+ALTER TABLE foods
+  ADD COLUMN quantity DOUBLE PRECISION CHECK (quantity > 0),
+  ADD COLUMN unit TEXT;
@@ -0,0 +1,18 @@
+CREATE TABLE storage_locations (
+  name TEXT PRIMARY KEY,
+  position INTEGER NOT NULL,
+  expiry_extension_days INTEGER CHECK (expiry_extension_days > 0)
+);
+
+INSERT INTO storage_locations (name, position, expiry_extension_days)
+VALUES ('fridge', 1, NULL), ('freezer', 2, 90), ('pantry', 3, NULL);
+
+ALTER TABLE foods
+  ADD COLUMN location TEXT NOT NULL DEFAULT 'fridge',
+  ADD COLUMN extended_best_before_date DATE,
+  ADD CONSTRAINT fk_location FOREIGN KEY (location) REFERENCES storage_locations (name) ON UPDATE CASCADE;
+
+-- New foods go to the first location, which may not be the fridge.
+ALTER TABLE foods ALTER COLUMN location DROP DEFAULT;
+
+CREATE INDEX foods_location_idx ON foods (location);
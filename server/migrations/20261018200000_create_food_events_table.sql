@@ -0,0 +1,14 @@
+CREATE TABLE food_events (
+  id SERIAL PRIMARY KEY,
+  -- Not a foreign key, the food is usually gone by then.
+  food_id INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  reason TEXT NOT NULL CHECK (reason IN ('consumed', 'thrown_away', 'given_away')),
+  quantity DOUBLE PRECISION,
+  unit TEXT,
+  location TEXT NOT NULL,
+  expires_on DATE NOT NULL,
+  happened_on DATE NOT NULL DEFAULT current_date
+);
+
+CREATE INDEX food_events_happened_on_idx ON food_events (happened_on);
@@ -1,25 +1,53 @@
-use serde::Deserialize;
-use tide::{Body, Request, Server};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use tide::{Body, Request, Response, Server, StatusCode};
+use uuid::Uuid;
 
-use crate::days::repository;
-use crate::domain::MealType;
-use crate::tide_utils::parse_iso_date_param;
+use crate::days::repository::{self, SlotToRandomize};
+use crate::domain::{Day, DayV0};
+use crate::meal_slots::repository::{get_meal_slot, get_meal_slots, DINNER, LUNCH};
+use crate::recipes::repository::{get_recipe, RecipeFilter};
+use crate::tide_utils::{parse_date_range_query, parse_iso_date_param, split_list};
 use crate::AppContext;
 
 pub fn init(app: &mut Server<AppContext>) {
     let mut days_api = app.at("/api/v0/days");
-    days_api.at("/:date").get(get_day);
-    days_api.at("/:date/randomize").put(randomize_day);
+    days_api.get(get_days_v0);
+    days_api.at("/randomize").put(randomize_days_v0);
+    days_api.at("/:date").get(get_day_v0);
+    days_api.at("/:date").delete(clear_day);
+    days_api.at("/:date/randomize").put(randomize_day_v0);
+    days_api.at("/:date/lunch").put(set_lunch);
+    days_api.at("/:date/dinner").put(set_dinner);
+    days_api.at("/:date/lunch").delete(clear_lunch);
+    days_api.at("/:date/dinner").delete(clear_dinner);
     days_api.at("/:date/lunch/randomize").put(randomize_lunch);
     days_api.at("/:date/dinner/randomize").put(randomize_dinner);
     days_api.at("/:date/lunch/cheat").put(cheat_lunch);
     days_api.at("/:date/dinner/cheat").put(cheat_dinner);
+    days_api.at("/:date/lunch/lock").put(lock_lunch);
+    days_api.at("/:date/dinner/lock").put(lock_dinner);
+    days_api.at("/:date/lunch/lock").delete(unlock_lunch);
+    days_api.at("/:date/dinner/lock").delete(unlock_dinner);
+
+    let mut days_v1_api = app.at("/api/v1/days");
+    days_v1_api.get(get_days);
+    days_v1_api.at("/randomize").put(randomize_days);
+    days_v1_api.at("/:date").get(get_day);
+    days_v1_api.at("/:date").delete(clear_day);
+    days_v1_api.at("/:date/randomize").put(randomize_day);
+    days_v1_api.at("/:date/:slot").put(set_slot);
+    days_v1_api.at("/:date/:slot").delete(clear_slot);
+    days_v1_api.at("/:date/:slot/randomize").put(randomize_slot);
+    days_v1_api.at("/:date/:slot/cheat").put(cheat_slot);
+    days_v1_api.at("/:date/:slot/lock").put(lock_slot);
+    days_v1_api.at("/:date/:slot/lock").delete(unlock_slot);
 }
 
-async fn get_day(req: Request<AppContext>) -> tide::Result<Body> {
-    let date = parse_iso_date_param(&req, "date")?;
-    let day = repository::get_day(&req.state().pool, date).await?;
-    Body::from_json(&day)
+#[derive(Serialize)]
+struct GetDaysResponse<D> {
+    days: Vec<D>,
 }
 
 #[derive(Deserialize)]
@@ -28,38 +56,370 @@ struct RandomizeQuery {
     quick: bool,
 }
 
-async fn randomize_day(req: Request<AppContext>) -> tide::Result<Body> {
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RandomizeRangeQueryV0 {
+    #[serde(default)]
+    quick: bool,
+    #[serde(default)]
+    lunch_quick: Option<bool>,
+    #[serde(default)]
+    dinner_quick: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RandomizeQueryV1 {
+    #[serde(default)]
+    quick: bool,
+    /// Comma separated list of slots restricted to quick recipes, on top of `quick`.
+    #[serde(default)]
+    quick_slots: Option<String>,
+}
+
+/// Filters accepted by every randomize endpoint.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FilterQuery {
+    /// Comma separated.
+    #[serde(default)]
+    include_tags: Option<String>,
+    /// Comma separated.
+    #[serde(default)]
+    exclude_tags: Option<String>,
+    /// Avoids recipes planned this many days or less around the date.
+    #[serde(default = "default_recent_days")]
+    recent_days: i32,
+    /// Past meals count half as much towards how often a recipe was eaten
+    /// every this many days.
+    #[serde(default = "default_half_life_days")]
+    half_life_days: f64,
+    /// Favours recipes using foods expiring within this many days.
+    #[serde(default)]
+    expiring_within_days: Option<i32>,
+}
+
+fn default_recent_days() -> i32 {
+    3
+}
+
+fn default_half_life_days() -> f64 {
+    90.0
+}
+
+fn recipe_filter(req: &Request<AppContext>, quick: bool) -> tide::Result<RecipeFilter> {
+    let query: FilterQuery = req.query()?;
+    if query.recent_days < 0 || !(query.half_life_days.is_finite() && query.half_life_days > 0.0) {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Query params 'recentDays' and 'halfLifeDays' should be positive",
+        ));
+    }
+    if matches!(query.expiring_within_days, Some(days) if days < 0) {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Query param 'expiringWithinDays' can't be negative",
+        ));
+    }
+
+    Ok(RecipeFilter {
+        quick,
+        include_tags: split_list(query.include_tags.as_deref()),
+        exclude_tags: split_list(query.exclude_tags.as_deref()),
+        recent_days: query.recent_days,
+        half_life_days: query.half_life_days,
+        expiring_within_days: query.expiring_within_days,
+    })
+}
+
+#[derive(Deserialize)]
+struct SeedQuery {
+    /// Picks the same recipes every time for the same seed and data.
+    #[serde(default)]
+    seed: Option<u64>,
+}
+
+fn rng(req: &Request<AppContext>) -> tide::Result<StdRng> {
+    let query: SeedQuery = req.query()?;
+    Ok(match query.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetMealBody {
+    recipe_id: Uuid,
+}
+
+async fn ensure_slot(req: &Request<AppContext>, name: &str, randomizing: bool) -> tide::Result<()> {
+    match get_meal_slot(&req.state().pool, name).await? {
+        None => Err(tide::Error::from_str(
+            StatusCode::NotFound,
+            format!("Unknown meal slot '{}'", name),
+        )),
+        Some(slot) if randomizing && !slot.randomizable => Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!("Meal slot '{}' cannot be randomized", name),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+async fn randomize_slot_meal(req: &Request<AppContext>, slot: &str) -> tide::Result<Day> {
+    ensure_slot(req, slot, true).await?;
     let query: RandomizeQuery = req.query()?;
+    let date = parse_iso_date_param(req, "date")?;
+    let slots = [SlotToRandomize {
+        slot: slot.to_string(),
+        filter: recipe_filter(req, query.quick)?,
+    }];
+    Ok(repository::randomize_meals(&req.state().pool, date, &slots, &mut rng(req)?).await?)
+}
+
+async fn cheat_slot_meal(req: &Request<AppContext>, slot: &str) -> tide::Result<Day> {
+    ensure_slot(req, slot, false).await?;
+    let date = parse_iso_date_param(req, "date")?;
+    Ok(repository::cheat_meal(&req.state().pool, date, slot).await?)
+}
+
+async fn lock_slot_meal(req: &Request<AppContext>, slot: &str, locked: bool) -> tide::Result<Day> {
+    ensure_slot(req, slot, false).await?;
+    let date = parse_iso_date_param(req, "date")?;
+    let pool = &req.state().pool;
+    if locked {
+        Ok(repository::lock_meal(pool, date, slot).await?)
+    } else {
+        Ok(repository::unlock_meal(pool, date, slot).await?)
+    }
+}
+
+async fn set_slot_meal(req: &mut Request<AppContext>, slot: &str) -> tide::Result<Day> {
+    ensure_slot(req, slot, false).await?;
+    let date = parse_iso_date_param(req, "date")?;
+    let body: SetMealBody = req.body_json().await?;
+    let pool = &req.state().pool;
+
+    match get_recipe(pool, body.recipe_id).await? {
+        Some(recipe) if recipe.archived_at.is_some() => Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!("Recipe '{}' is archived", body.recipe_id),
+        )),
+        Some(recipe) => Ok(repository::set_recipe(pool, date, slot, recipe.id).await?),
+        None => Err(tide::Error::from_str(
+            StatusCode::NotFound,
+            format!("Unknown recipe '{}'", body.recipe_id),
+        )),
+    }
+}
+
+async fn clear_slot_meal(req: &Request<AppContext>, slot: &str) -> tide::Result<Day> {
+    ensure_slot(req, slot, false).await?;
+    let date = parse_iso_date_param(req, "date")?;
+    Ok(repository::clear_meal(&req.state().pool, date, slot).await?)
+}
+
+async fn clear_day(req: Request<AppContext>) -> tide::Result<Response> {
     let date = parse_iso_date_param(&req, "date")?;
-    let day =
-        repository::randomize_meal(&req.state().pool, date, MealType::Both, query.quick).await?;
-    Body::from_json(&day)
+    repository::clear_day(&req.state().pool, date).await?;
+    Ok(StatusCode::NoContent.into())
 }
 
-async fn randomize_lunch(req: Request<AppContext>) -> tide::Result<Body> {
-    let query: RandomizeQuery = req.query()?;
+// v0, lunch and dinner only
+
+async fn get_days_v0(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let days = repository::get_days(&req.state().pool, range.from, range.to).await?;
+    Body::from_json(&GetDaysResponse {
+        days: days.into_iter().map(DayV0::from).collect(),
+    })
+}
+
+async fn get_day_v0(req: Request<AppContext>) -> tide::Result<Body> {
     let date = parse_iso_date_param(&req, "date")?;
-    let day =
-        repository::randomize_meal(&req.state().pool, date, MealType::Lunch, query.quick).await?;
-    Body::from_json(&day)
+    let day = repository::get_day(&req.state().pool, date).await?;
+    Body::from_json(&DayV0::from(day))
 }
 
-async fn randomize_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+async fn randomize_days_v0(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let query: RandomizeRangeQueryV0 = req.query()?;
+    let slots = [
+        SlotToRandomize {
+            slot: LUNCH.to_string(),
+            filter: recipe_filter(&req, query.lunch_quick.unwrap_or(query.quick))?,
+        },
+        SlotToRandomize {
+            slot: DINNER.to_string(),
+            filter: recipe_filter(&req, query.dinner_quick.unwrap_or(query.quick))?,
+        },
+    ];
+    let days = repository::randomize_days(
+        &req.state().pool,
+        range.from,
+        range.to,
+        &slots,
+        &mut rng(&req)?,
+    )
+    .await?;
+    Body::from_json(&GetDaysResponse {
+        days: days.into_iter().map(DayV0::from).collect(),
+    })
+}
+
+async fn randomize_day_v0(req: Request<AppContext>) -> tide::Result<Body> {
     let query: RandomizeQuery = req.query()?;
     let date = parse_iso_date_param(&req, "date")?;
-    let day =
-        repository::randomize_meal(&req.state().pool, date, MealType::Dinner, query.quick).await?;
-    Body::from_json(&day)
+    let filter = recipe_filter(&req, query.quick)?;
+    let slots = [LUNCH, DINNER].map(|slot| SlotToRandomize {
+        slot: slot.to_string(),
+        filter: filter.clone(),
+    });
+    let day = repository::randomize_meals(&req.state().pool, date, &slots, &mut rng(&req)?).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn randomize_lunch(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = randomize_slot_meal(&req, LUNCH).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn randomize_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = randomize_slot_meal(&req, DINNER).await?;
+    Body::from_json(&DayV0::from(day))
 }
 
 async fn cheat_lunch(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = cheat_slot_meal(&req, LUNCH).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn cheat_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = cheat_slot_meal(&req, DINNER).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn lock_lunch(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = lock_slot_meal(&req, LUNCH, true).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn lock_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = lock_slot_meal(&req, DINNER, true).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn unlock_lunch(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = lock_slot_meal(&req, LUNCH, false).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn unlock_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = lock_slot_meal(&req, DINNER, false).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn set_lunch(mut req: Request<AppContext>) -> tide::Result<Body> {
+    let day = set_slot_meal(&mut req, LUNCH).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn set_dinner(mut req: Request<AppContext>) -> tide::Result<Body> {
+    let day = set_slot_meal(&mut req, DINNER).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn clear_lunch(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = clear_slot_meal(&req, LUNCH).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+async fn clear_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = clear_slot_meal(&req, DINNER).await?;
+    Body::from_json(&DayV0::from(day))
+}
+
+// v1, any configured meal slot
+
+async fn slots_to_randomize(req: &Request<AppContext>) -> tide::Result<Vec<SlotToRandomize>> {
+    let query: RandomizeQueryV1 = req.query()?;
+    let quick_slots = split_list(query.quick_slots.as_deref());
+
+    get_meal_slots(&req.state().pool)
+        .await?
+        .into_iter()
+        .filter(|s| s.randomizable)
+        .map(|s| {
+            let quick = query.quick || quick_slots.contains(&s.name);
+            Ok(SlotToRandomize {
+                slot: s.name,
+                filter: recipe_filter(req, quick)?,
+            })
+        })
+        .collect()
+}
+
+async fn get_days(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let days = repository::get_days(&req.state().pool, range.from, range.to).await?;
+    Body::from_json(&GetDaysResponse { days })
+}
+
+async fn get_day(req: Request<AppContext>) -> tide::Result<Body> {
     let date = parse_iso_date_param(&req, "date")?;
-    let day = repository::cheat_meal(&req.state().pool, date, MealType::Lunch).await?;
+    let day = repository::get_day(&req.state().pool, date).await?;
     Body::from_json(&day)
 }
 
-async fn cheat_dinner(req: Request<AppContext>) -> tide::Result<Body> {
+async fn randomize_days(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let slots = slots_to_randomize(&req).await?;
+    let days = repository::randomize_days(
+        &req.state().pool,
+        range.from,
+        range.to,
+        &slots,
+        &mut rng(&req)?,
+    )
+    .await?;
+    Body::from_json(&GetDaysResponse { days })
+}
+
+async fn randomize_day(req: Request<AppContext>) -> tide::Result<Body> {
     let date = parse_iso_date_param(&req, "date")?;
-    let day = repository::cheat_meal(&req.state().pool, date, MealType::Dinner).await?;
+    let slots = slots_to_randomize(&req).await?;
+    let day = repository::randomize_meals(&req.state().pool, date, &slots, &mut rng(&req)?).await?;
+    Body::from_json(&day)
+}
+
+async fn randomize_slot(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = randomize_slot_meal(&req, req.param("slot")?).await?;
+    Body::from_json(&day)
+}
+
+async fn cheat_slot(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = cheat_slot_meal(&req, req.param("slot")?).await?;
+    Body::from_json(&day)
+}
+
+async fn lock_slot(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = lock_slot_meal(&req, req.param("slot")?, true).await?;
+    Body::from_json(&day)
+}
+
+async fn unlock_slot(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = lock_slot_meal(&req, req.param("slot")?, false).await?;
+    Body::from_json(&day)
+}
+
+async fn set_slot(mut req: Request<AppContext>) -> tide::Result<Body> {
+    let slot = req.param("slot")?.to_string();
+    let day = set_slot_meal(&mut req, &slot).await?;
+    Body::from_json(&day)
+}
+
+async fn clear_slot(req: Request<AppContext>) -> tide::Result<Body> {
+    let day = clear_slot_meal(&req, req.param("slot")?).await?;
     Body::from_json(&day)
 }
@@ -1,200 +1,401 @@
-use sqlx::{Executor, Postgres};
-use time::Date;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::anyhow;
+use rand::Rng;
+use sqlx::{Acquire, Executor, Postgres};
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
-use crate::domain::{Day, Meal, MealType};
-use crate::recipes::repository::{get_random_recipe, get_recipe};
+use crate::domain::{Day, Ingredient, Meal};
+use crate::foods::repository::get_expiring_food_counts;
+use crate::recipes::randomizer::{candidates_on, pick_recipe};
+use crate::recipes::repository::{get_ingredients, get_random_recipe_ids, RecipeDb, RecipeFilter};
 
 pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> + Clone {}
 impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> + Clone {}
 
 #[derive(sqlx::FromRow)]
-pub struct DayDb {
-    pub date: Date,
-    pub lunch_id: Option<Uuid>,
-    pub dinner_id: Option<Uuid>,
-    pub lunch_is_cheat: bool,
-    pub dinner_is_cheat: bool,
+struct PlannedMealDb {
+    date: Date,
+    slot: String,
+    is_cheat: bool,
+    locked: bool,
+    recipe_id: Option<Uuid>,
+    recipe_name: Option<String>,
+    recipe_quick: Option<bool>,
+    recipe_body: Option<String>,
+    recipe_servings: Option<i32>,
+    recipe_tags: Option<Vec<String>>,
+    recipe_archived_at: Option<OffsetDateTime>,
 }
 
-pub async fn get_day<'a, E: PgExecutor<'a>>(exec: E, date: Date) -> anyhow::Result<Day> {
-    let maybe_daydb: Option<DayDb> = sqlx::query_as(
-        "SELECT *
-         FROM days
-         WHERE date = $1",
+impl PlannedMealDb {
+    fn recipe(&mut self) -> Option<RecipeDb> {
+        match (
+            self.recipe_id,
+            self.recipe_name.take(),
+            self.recipe_quick,
+            self.recipe_body.take(),
+        ) {
+            (Some(id), Some(name), Some(quick), Some(body)) => Some(RecipeDb {
+                id,
+                name,
+                quick,
+                body,
+                servings: self.recipe_servings,
+                tags: self.recipe_tags.take().unwrap_or_default(),
+                archived_at: self.recipe_archived_at,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn to_meal(
+    recipe: Option<RecipeDb>,
+    is_cheat: bool,
+    ingredients: &HashMap<Uuid, Vec<Ingredient>>,
+) -> Meal {
+    match recipe {
+        Some(recipe) => {
+            // The same recipe can appear several times in a range.
+            let recipe_ingredients = ingredients.get(&recipe.id).cloned().unwrap_or_default();
+            Meal::Recipe(recipe.into_recipe(recipe_ingredients))
+        }
+        None if is_cheat => Meal::Cheat,
+        None => Meal::Unset,
+    }
+}
+
+pub async fn get_days<'a, E: PgExecutor<'a>>(
+    exec: E,
+    from: Date,
+    to: Date,
+) -> anyhow::Result<Vec<Day>> {
+    let rows: Vec<PlannedMealDb> = sqlx::query_as(
+        "SELECT
+           dates.date::date AS date,
+           s.name AS slot,
+           coalesce(pm.is_cheat, False) AS is_cheat,
+           coalesce(pm.locked, False) AS locked,
+           r.id AS recipe_id, r.name AS recipe_name, r.quick AS recipe_quick, r.body_html AS recipe_body,
+           r.servings AS recipe_servings, r.archived_at AS recipe_archived_at,
+           (SELECT array_agg(t.tag ORDER BY t.tag) FROM recipe_tags t WHERE t.recipe_id = r.id) AS recipe_tags
+         FROM generate_series($1::date, $2::date, interval '1 day') AS dates(date)
+         CROSS JOIN meal_slots s
+         LEFT JOIN planned_meals pm ON pm.date = dates.date AND pm.slot = s.name
+         LEFT JOIN recipes r ON r.id = pm.recipe_id
+         ORDER BY dates.date, s.position",
     )
-    .bind(date)
-    .fetch_optional(exec.clone())
+    .bind(from)
+    .bind(to)
+    .fetch_all(exec.clone())
     .await?;
 
-    if let Some(daydb) = maybe_daydb {
-        let mut day = Day {
-            date: daydb.date,
-            lunch: Meal::Unset,
-            dinner: Meal::Unset,
-        };
-
-        if let Some(lunch_id) = daydb.lunch_id {
-            day.lunch = match get_recipe(exec.clone(), lunch_id).await? {
-                Option::Some(recipe) => Meal::Recipe(recipe),
-                Option::None => Meal::Unset,
-            };
-        } else if daydb.lunch_is_cheat {
-            day.lunch = Meal::Cheat;
-        }
+    let recipe_ids: Vec<Uuid> = rows.iter().filter_map(|r| r.recipe_id).collect();
+    let ingredients = get_ingredients(exec, &recipe_ids).await?;
 
-        if let Some(dinner_id) = daydb.dinner_id {
-            day.dinner = match get_recipe(exec.clone(), dinner_id).await? {
-                Option::Some(recipe) => Meal::Recipe(recipe),
-                Option::None => Meal::Unset,
-            };
-        } else if daydb.dinner_is_cheat {
-            day.dinner = Meal::Cheat;
+    let mut meals_by_date: HashMap<Date, BTreeMap<String, Meal>> = HashMap::new();
+    let mut locked_by_date: HashMap<Date, Vec<String>> = HashMap::new();
+    for mut row in rows {
+        if row.locked {
+            locked_by_date
+                .entry(row.date)
+                .or_default()
+                .push(row.slot.clone());
         }
+        let meal = to_meal(row.recipe(), row.is_cheat, &ingredients);
+        meals_by_date
+            .entry(row.date)
+            .or_default()
+            .insert(row.slot, meal);
+    }
 
-        Ok(day)
-    } else {
-        Ok(Day {
+    let mut days = Vec::new();
+    let mut date = from;
+    while date <= to {
+        days.push(Day {
             date,
-            lunch: Meal::Unset,
-            dinner: Meal::Unset,
-        })
+            meals: meals_by_date.remove(&date).unwrap_or_default(),
+            locked_slots: locked_by_date.remove(&date).unwrap_or_default(),
+        });
+        date = match date.next_day() {
+            Some(next_day) => next_day,
+            None => break,
+        };
     }
+
+    Ok(days)
 }
 
-pub async fn randomize_meal<'a, E: PgExecutor<'a>>(
+pub async fn get_day<'a, E: PgExecutor<'a>>(exec: E, date: Date) -> anyhow::Result<Day> {
+    get_days(exec, date, date)
+        .await?
+        .pop()
+        .ok_or_else(|| anyhow!("No day returned for {}", date))
+}
+
+pub async fn get_planned_recipe_ids<'a, E: PgExecutor<'a>>(
     exec: E,
-    date: Date,
-    meal: MealType,
-    quick: bool,
-) -> anyhow::Result<Day> {
-    match meal {
-        MealType::Lunch => {
-            let maybe_recipe = get_random_recipe(exec.clone(), quick).await?;
-            if let Some(recipe) = maybe_recipe {
-                let day: DayDb = sqlx::query_as(
-                    "INSERT INTO days (date, lunch_id, lunch_is_cheat)
-                     VALUES ($1, $2, False)
-                     ON CONFLICT (date) DO
-                     UPDATE SET lunch_id = $2, lunch_is_cheat = False
-                     RETURNING *",
-                )
-                .bind(date)
-                .bind(recipe.id)
-                .fetch_one(exec.clone())
-                .await?;
-
-                Ok(get_day(exec, day.date).await?)
-            } else {
-                Ok(Day {
-                    date,
-                    lunch: Meal::Unset,
-                    dinner: Meal::Unset,
-                })
-            }
+    from: Date,
+    to: Date,
+) -> anyhow::Result<Vec<Uuid>> {
+    let ids = sqlx::query_scalar(
+        "SELECT recipe_id FROM planned_meals
+         WHERE date BETWEEN $1 AND $2 AND recipe_id IS NOT NULL",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(exec)
+    .await?;
+
+    Ok(ids)
+}
+
+/// A slot to fill when randomizing, picking among recipes matching `filter`.
+pub struct SlotToRandomize {
+    pub slot: String,
+    pub filter: RecipeFilter,
+}
+
+const SET_RECIPE: &str = "
+    INSERT INTO planned_meals (date, slot, recipe_id, is_cheat)
+    VALUES ($1, $2, $3, False)
+    ON CONFLICT (date, slot) DO
+    UPDATE SET recipe_id = $3, is_cheat = False";
+
+/// Randomizes the given slots of every day in the range inside a single transaction.
+/// A recipe is not picked twice in the range until every eligible recipe has been used,
+/// recently planned recipes are only picked when nothing else is left.
+/// Slots that don't exist, are not randomizable or are locked are left untouched.
+pub async fn randomize_days<'a, E, R>(
+    exec: E,
+    from: Date,
+    to: Date,
+    slots: &[SlotToRandomize],
+    rng: &mut R,
+) -> anyhow::Result<Vec<Day>>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+    R: Rng + Send + ?Sized,
+{
+    let mut tx = exec.clone().begin().await?;
+
+    let randomizable: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM meal_slots WHERE randomizable")
+            .fetch_all(&mut *tx)
+            .await?;
+    let slots: Vec<&SlotToRandomize> = slots
+        .iter()
+        .filter(|s| randomizable.contains(&s.slot))
+        .collect();
+
+    let locked: Vec<(Date, String, Option<Uuid>)> = sqlx::query_as(
+        "SELECT date, slot, recipe_id FROM planned_meals
+         WHERE locked AND date BETWEEN $1 AND $2",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *tx)
+    .await?;
+    let is_locked = |date: Date, slot: &str| locked.iter().any(|l| l.0 == date && l.1 == slot);
+
+    // Foods don't change while randomizing, counted once per number of days.
+    let mut expiring_food_counts: HashMap<i32, HashMap<Uuid, i64>> = HashMap::new();
+    for within_days in slots.iter().filter_map(|s| s.filter.expiring_within_days) {
+        if let Entry::Vacant(entry) = expiring_food_counts.entry(within_days) {
+            entry.insert(get_expiring_food_counts(&mut *tx, within_days).await?);
         }
-        MealType::Dinner => {
-            let maybe_recipe = get_random_recipe(exec.clone(), quick).await?;
-            if let Some(recipe) = maybe_recipe {
-                let day: DayDb = sqlx::query_as(
-                    "INSERT INTO days (date, dinner_id, dinner_is_cheat)
-                     VALUES ($1, $2, False)
-                     ON CONFLICT (date) DO
-                     UPDATE SET dinner_id = $2, dinner_is_cheat = False
-                     RETURNING *",
-                )
-                .bind(date)
-                .bind(recipe.id)
-                .fetch_one(exec.clone())
-                .await?;
-
-                Ok(get_day(exec, day.date).await?)
-            } else {
-                Ok(Day {
-                    date,
-                    lunch: Meal::Unset,
-                    dinner: Meal::Unset,
-                })
-            }
+    }
+
+    // Meals being replaced don't count, locked ones are kept.
+    let slot_names: Vec<String> = slots.iter().map(|s| s.slot.clone()).collect();
+    let planned: Vec<(Uuid, Date)> = sqlx::query_as(
+        "SELECT recipe_id, date FROM planned_meals
+         WHERE recipe_id IS NOT NULL
+           AND NOT (date BETWEEN $1 AND $2 AND slot = ANY($3) AND NOT locked)",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(&slot_names)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut history: HashMap<Uuid, Vec<Date>> = HashMap::new();
+    for (recipe_id, date) in planned {
+        history.entry(recipe_id).or_default().push(date);
+    }
+
+    // Recipes matching a filter, fetched once for the slots sharing it.
+    let filter_key = |f: &RecipeFilter| (f.quick, f.include_tags.clone(), f.exclude_tags.clone());
+    let mut recipe_ids = HashMap::new();
+    for slot in &slots {
+        if let Entry::Vacant(entry) = recipe_ids.entry(filter_key(&slot.filter)) {
+            entry.insert(get_random_recipe_ids(&mut *tx, &slot.filter).await?);
         }
-        MealType::Both => {
-            let recipes = (
-                get_random_recipe(exec.clone(), quick).await?,
-                get_random_recipe(exec.clone(), quick).await?,
+    }
+
+    // Locked meals stay in the range, they count as already picked.
+    let mut picked: Vec<Uuid> = locked.iter().filter_map(|l| l.2).collect();
+    let mut date = from;
+    while date <= to {
+        for slot in &slots {
+            if is_locked(date, &slot.slot) {
+                continue;
+            }
+
+            let mut candidates = candidates_on(
+                date,
+                &recipe_ids[&filter_key(&slot.filter)],
+                &history,
+                slot.filter.recent_days,
+                slot.filter.half_life_days,
             );
-            if let (Some(lunch_recipe), Some(dinner_recipe)) = recipes {
-                let day: DayDb = sqlx::query_as(
-                    "INSERT INTO days (date, lunch_id, lunch_is_cheat, dinner_id, dinner_is_cheat)
-                     VALUES ($1, $2, False, $3, False)
-                     ON CONFLICT (date) DO
-                     UPDATE SET lunch_id = $2, lunch_is_cheat = False, dinner_id = $3, dinner_is_cheat = False
-                     RETURNING *",
-                )
-                .bind(date)
-                .bind(lunch_recipe.id)
-                .bind(dinner_recipe.id)
-                .fetch_one(exec.clone())
-                .await?;
-
-                Ok(get_day(exec, day.date).await?)
-            } else {
-                Ok(Day {
-                    date,
-                    lunch: Meal::Unset,
-                    dinner: Meal::Unset,
-                })
+            if let Some(counts) = slot
+                .filter
+                .expiring_within_days
+                .and_then(|days| expiring_food_counts.get(&days))
+            {
+                for candidate in &mut candidates {
+                    candidate.expiring_foods = counts.get(&candidate.id).copied().unwrap_or(0);
+                }
+            }
+
+            if let Some(recipe_id) = pick_recipe(rng, &candidates, &mut picked) {
+                sqlx::query(SET_RECIPE)
+                    .bind(date)
+                    .bind(&slot.slot)
+                    .bind(recipe_id)
+                    .execute(&mut *tx)
+                    .await?;
             }
         }
+
+        date = match date.next_day() {
+            Some(next_day) => next_day,
+            None => break,
+        };
     }
+
+    tx.commit().await?;
+
+    get_days(exec, from, to).await
+}
+
+pub async fn randomize_meals<'a, E, R>(
+    exec: E,
+    date: Date,
+    slots: &[SlotToRandomize],
+    rng: &mut R,
+) -> anyhow::Result<Day>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+    R: Rng + Send + ?Sized,
+{
+    randomize_days(exec.clone(), date, date, slots, rng).await?;
+
+    get_day(exec, date).await
+}
+
+pub async fn set_recipe<'a, E: PgExecutor<'a>>(
+    exec: E,
+    date: Date,
+    slot: &str,
+    recipe_id: Uuid,
+) -> anyhow::Result<Day> {
+    sqlx::query(SET_RECIPE)
+        .bind(date)
+        .bind(slot)
+        .bind(recipe_id)
+        .execute(exec.clone())
+        .await?;
+
+    get_day(exec, date).await
 }
 
 pub async fn cheat_meal<'a, E: PgExecutor<'a>>(
     exec: E,
     date: Date,
-    meal: MealType,
+    slot: &str,
 ) -> anyhow::Result<Day> {
-    match meal {
-        MealType::Lunch => {
-            let day: DayDb = sqlx::query_as(
-                "INSERT INTO days (date, lunch_id, lunch_is_cheat)
-                 VALUES ($1, Null, True)
-                 ON CONFLICT (date) DO
-                 UPDATE SET lunch_id = Null, lunch_is_cheat = True
-                 RETURNING *",
-            )
-            .bind(date)
-            .fetch_one(exec.clone())
-            .await?;
+    sqlx::query(
+        "INSERT INTO planned_meals (date, slot, recipe_id, is_cheat)
+         VALUES ($1, $2, Null, True)
+         ON CONFLICT (date, slot) DO
+         UPDATE SET recipe_id = Null, is_cheat = True",
+    )
+    .bind(date)
+    .bind(slot)
+    .execute(exec.clone())
+    .await?;
 
-            Ok(get_day(exec, day.date).await?)
-        }
-        MealType::Dinner => {
-            let day: DayDb = sqlx::query_as(
-                "INSERT INTO days (date, dinner_id, dinner_is_cheat)
-                 VALUES ($1, Null, True)
-                 ON CONFLICT (date) DO
-                 UPDATE SET dinner_id = Null, dinner_is_cheat = True
-                 RETURNING *",
-            )
-            .bind(date)
-            .fetch_one(exec.clone())
-            .await?;
+    get_day(exec, date).await
+}
 
-            Ok(get_day(exec, day.date).await?)
-        }
-        MealType::Both => {
-            let day: DayDb = sqlx::query_as(
-                "INSERT INTO days (date, lunch_id, lunch_is_cheat, dinner_id, dinner_is_cheat)
-                 VALUES ($1, Null, True, Null, True)
-                 ON CONFLICT (date) DO
-                 UPDATE SET lunch_id = Null, lunch_is_cheat = True, lunch_id = Null, lunch_is_cheat = True
-                 RETURNING *",
-            )
-            .bind(date)
-            .fetch_one(exec.clone())
-            .await?;
+/// Keeps the meal as it is when randomizing.
+pub async fn lock_meal<'a, E: PgExecutor<'a>>(
+    exec: E,
+    date: Date,
+    slot: &str,
+) -> anyhow::Result<Day> {
+    sqlx::query(
+        "INSERT INTO planned_meals (date, slot, locked)
+         VALUES ($1, $2, True)
+         ON CONFLICT (date, slot) DO
+         UPDATE SET locked = True",
+    )
+    .bind(date)
+    .bind(slot)
+    .execute(exec.clone())
+    .await?;
 
-            Ok(get_day(exec, day.date).await?)
-        }
-    }
+    get_day(exec, date).await
+}
+
+pub async fn unlock_meal<'a, E: PgExecutor<'a>>(
+    exec: E,
+    date: Date,
+    slot: &str,
+) -> anyhow::Result<Day> {
+    // Nothing left to keep for an empty slot.
+    sqlx::query(
+        "DELETE FROM planned_meals
+         WHERE date = $1 AND slot = $2 AND recipe_id IS NULL AND NOT is_cheat",
+    )
+    .bind(date)
+    .bind(slot)
+    .execute(exec.clone())
+    .await?;
+
+    sqlx::query("UPDATE planned_meals SET locked = False WHERE date = $1 AND slot = $2")
+        .bind(date)
+        .bind(slot)
+        .execute(exec.clone())
+        .await?;
+
+    get_day(exec, date).await
+}
+
+/// Empties the slot, unlocking it.
+pub async fn clear_meal<'a, E: PgExecutor<'a>>(
+    exec: E,
+    date: Date,
+    slot: &str,
+) -> anyhow::Result<Day> {
+    sqlx::query("DELETE FROM planned_meals WHERE date = $1 AND slot = $2")
+        .bind(date)
+        .bind(slot)
+        .execute(exec.clone())
+        .await?;
+
+    get_day(exec, date).await
+}
+
+pub async fn clear_day<'a, E: PgExecutor<'a>>(exec: E, date: Date) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM planned_meals WHERE date = $1")
+        .bind(date)
+        .execute(exec)
+        .await?;
+
+    Ok(())
 }
@@ -1,5 +1,7 @@
-use serde::{Deserialize, Serialize};
-use time::Date;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::serde_iso_date;
@@ -10,25 +12,118 @@ pub struct NewRecipe {
     pub name: String,
     pub quick: bool,
     pub body: String,
+    /// How many people the ingredients feed, unknown for most older recipes.
+    /// Left as it is when updating without it, `null` clears it.
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub servings: Option<Option<i32>>,
+    /// Left as they are when updating without them.
+    #[serde(default)]
+    pub ingredients: Option<Vec<Ingredient>>,
+    /// Left as they are when updating without them.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Tells a field set to `null`, deserialized as `Some(None)`, apart from a missing one.
+fn deserialize_present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
-#[derive(Debug, Serialize, sqlx::FromRow)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Recipe {
     pub id: Uuid,
     pub name: String,
     pub quick: bool,
-    #[sqlx(rename = "body_html")]
     pub body: String,
+    pub servings: Option<i32>,
+    pub ingredients: Vec<Ingredient>,
+    pub tags: Vec<String>,
+    /// Set when the recipe was deleted, it is kept so past days can still show it.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Ingredient {
+    pub name: String,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub note: Option<String>,
+}
+
+/// A previous version of a recipe, saved each time the recipe is updated.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeRevision {
+    pub recipe_id: Uuid,
+    pub revision: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub name: String,
+    pub quick: bool,
+    pub body: String,
+    pub servings: Option<i32>,
+    pub ingredients: Vec<Ingredient>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeRevisionSummary {
+    pub revision: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub name: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    pub count: i64,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Day {
+    #[serde(with = "serde_iso_date")]
+    pub date: Date,
+    pub meals: BTreeMap<String, Meal>,
+    /// Slots kept as they are when randomizing.
+    pub locked_slots: Vec<String>,
+}
+
+/// Shape of a day before meal slots were configurable, still served by the v0 API.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayV0 {
     #[serde(with = "serde_iso_date")]
     pub date: Date,
     pub lunch: Meal,
     pub dinner: Meal,
+    /// Left out when empty so the shape doesn't change for older clients.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub locked_slots: Vec<String>,
+}
+
+impl From<Day> for DayV0 {
+    fn from(mut day: Day) -> Self {
+        DayV0 {
+            date: day.date,
+            lunch: day.meals.remove("lunch").unwrap_or(Meal::Unset),
+            dinner: day.meals.remove("dinner").unwrap_or(Meal::Unset),
+            locked_slots: day
+                .locked_slots
+                .into_iter()
+                .filter(|s| s == "lunch" || s == "dinner")
+                .collect(),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -40,10 +135,22 @@ pub enum Meal {
     Unset,
 }
 
-pub enum MealType {
-    Lunch,
-    Dinner,
-    Both,
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MealSlot {
+    pub name: String,
+    pub position: i32,
+    pub randomizable: bool,
+}
+
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageLocation {
+    pub name: String,
+    pub position: i32,
+    /// Days added to the best before date of foods moved here, if any.
+    #[serde(default)]
+    pub expiry_extension_days: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -52,6 +159,14 @@ pub struct NewFood {
     pub name: String,
     #[serde(with = "serde_iso_date")]
     pub best_before_date: Date,
+    /// How much is left, unknown when missing.
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// The first storage location when missing.
+    #[serde(default)]
+    pub location: Option<String>,
 }
 
 #[derive(Serialize, sqlx::FromRow)]
@@ -61,4 +176,174 @@ pub struct Food {
     pub name: String,
     #[serde(with = "serde_iso_date")]
     pub best_before_date: Date,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub location: String,
+    /// Replaces the best before date once moved somewhere it keeps longer,
+    /// dropped when it leaves for somewhere it doesn't or the best before date changes.
+    #[serde(with = "serde_iso_date::option")]
+    pub extended_best_before_date: Option<Date>,
+}
+
+/// Changes to a food, missing fields are left as they are
+/// while `null` clears the quantity or unit.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoodPatch {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, with = "serde_iso_date::option")]
+    pub best_before_date: Option<Date>,
+    #[serde(default, deserialize_with = "present")]
+    pub quantity: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "present")]
+    pub unit: Option<Option<String>>,
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+/// A recipe using some of the foods about to expire.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeSuggestion {
+    pub recipe: RecipeSummary,
+    /// Soonest to expire first.
+    pub foods: Vec<ExpiringFood>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiringFood {
+    pub id: i32,
+    pub name: String,
+    #[serde(with = "serde_iso_date")]
+    pub expires_on: Date,
+}
+
+impl From<NewFood> for FoodPatch {
+    fn from(food: NewFood) -> Self {
+        FoodPatch {
+            name: Some(food.name),
+            best_before_date: Some(food.best_before_date),
+            quantity: Some(food.quantity),
+            unit: Some(food.unit),
+            location: food.location,
+        }
+    }
+}
+
+/// Tells a field set to `null` apart from a missing one, which gets the default `None`.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShoppingList {
+    #[serde(with = "serde_iso_date")]
+    pub from: Date,
+    #[serde(with = "serde_iso_date")]
+    pub to: Date,
+    pub items: Vec<ShoppingListItem>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShoppingListItem {
+    pub name: String,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub in_fridge: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    #[serde(with = "serde_iso_date")]
+    pub from: Date,
+    #[serde(with = "serde_iso_date")]
+    pub to: Date,
+    /// Recipes planned in the range, most planned first.
+    pub recipes: Vec<RecipeStats>,
+    pub cheat_meals: CheatMealStats,
+    pub quick_meals: QuickMealStats,
+    /// Recipes not planned once in the range, archived ones left out.
+    pub never_planned: Vec<RecipeSummary>,
+}
+
+/// What became of the foods that left between two dates.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoodStats {
+    #[serde(with = "serde_iso_date")]
+    pub from: Date,
+    #[serde(with = "serde_iso_date")]
+    pub to: Date,
+    /// Number of events, eating some of a food counting as one.
+    pub consumed: i64,
+    pub thrown_away: i64,
+    pub given_away: i64,
+    /// Foods thrown away in every month overlapping the range, including empty ones.
+    pub waste_by_month: Vec<PeriodCount>,
+    /// Most thrown away first, names compared ignoring case.
+    pub most_wasted: Vec<WastedFood>,
+    /// How many days before it expires a food is eaten, negative when eaten after.
+    pub average_days_before_expiry: Option<f64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WastedFood {
+    pub name: String,
+    pub times_thrown_away: i64,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeStats {
+    pub id: Uuid,
+    pub name: String,
+    pub quick: bool,
+    pub times_planned: i64,
+    #[serde(with = "serde_iso_date")]
+    pub last_planned: Date,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub quick: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheatMealStats {
+    pub total: i64,
+    /// Weeks start on Monday.
+    pub by_week: Vec<PeriodCount>,
+    pub by_month: Vec<PeriodCount>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodCount {
+    /// First day of the period, which can be before the start of the range.
+    #[serde(with = "serde_iso_date")]
+    pub start: Date,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickMealStats {
+    pub quick: i64,
+    pub not_quick: i64,
+    /// Share of planned recipes that are quick, missing when nothing was planned.
+    pub quick_ratio: Option<f64>,
 }
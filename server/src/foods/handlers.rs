@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use tide::{Body, Request, Response, Server, StatusCode};
 
-use super::repository;
-use crate::domain::{Food, NewFood};
+use super::repository::{self, Consumed, RemovalReason};
+use crate::domain::{Food, FoodPatch, NewFood, RecipeSuggestion, StorageLocation};
+use crate::storage_locations::repository::{get_storage_location, get_storage_locations};
 use crate::tide_utils::parse_param;
 use crate::AppContext;
 
@@ -10,12 +11,20 @@ pub fn init(app: &mut Server<AppContext>) {
     let mut foods_api = app.at("/api/v0/foods");
     foods_api.get(get_foods);
     foods_api.post(create_food);
+    foods_api.at("/by-location").get(get_foods_by_location);
+    foods_api.at("/suggestions").get(get_suggestions);
+    foods_api.at("/:id").put(replace_food);
+    foods_api.at("/:id").patch(update_food);
     foods_api.at("/:id").delete(delete_food);
+    foods_api.at("/:id/consume").post(consume_food);
+    foods_api.at("/:id/move").post(move_food);
 }
 
 #[derive(Deserialize)]
 struct GetFoodQueryParams {
     limit: Option<i32>,
+    /// Only lists the foods stored there.
+    location: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -26,20 +35,213 @@ struct GetFoodResponse {
 async fn get_foods(req: Request<AppContext>) -> tide::Result<Body> {
     let query: GetFoodQueryParams = req.query()?;
     let limit = query.limit.unwrap_or(500);
-    let foods = repository::get_foods(&req.state().pool, limit).await?;
+    let foods = repository::get_foods(&req.state().pool, query.location.as_deref(), limit).await?;
     Body::from_json(&GetFoodResponse { foods })
 }
 
+#[derive(Serialize)]
+struct FoodsInLocation {
+    name: String,
+    foods: Vec<Food>,
+}
+
+#[derive(Serialize)]
+struct GetFoodsByLocationResponse {
+    locations: Vec<FoodsInLocation>,
+}
+
+/// Every location in order, with the foods stored there, empty ones included.
+async fn get_foods_by_location(req: Request<AppContext>) -> tide::Result<Body> {
+    let pool = &req.state().pool;
+    let mut foods = repository::get_foods(pool, None, i32::MAX).await?;
+
+    let locations = get_storage_locations(pool)
+        .await?
+        .into_iter()
+        .map(|location| {
+            let (stored, others) = foods.drain(..).partition(|f| f.location == location.name);
+            foods = others;
+            FoodsInLocation {
+                name: location.name,
+                foods: stored,
+            }
+        })
+        .collect();
+
+    Body::from_json(&GetFoodsByLocationResponse { locations })
+}
+
+#[derive(Deserialize)]
+struct GetSuggestionsQuery {
+    /// Foods expiring within this many days from today are used up first.
+    #[serde(default = "default_suggestion_days")]
+    days: i32,
+    #[serde(default = "default_suggestion_limit")]
+    limit: usize,
+}
+
+fn default_suggestion_days() -> i32 {
+    3
+}
+
+fn default_suggestion_limit() -> usize {
+    10
+}
+
+#[derive(Serialize)]
+struct GetSuggestionsResponse {
+    suggestions: Vec<RecipeSuggestion>,
+}
+
+/// Recipes to cook so the foods about to expire don't go to waste.
+async fn get_suggestions(req: Request<AppContext>) -> tide::Result<Body> {
+    let query: GetSuggestionsQuery = req.query()?;
+    if query.days < 0 {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Days can't be negative",
+        ));
+    }
+
+    let suggestions =
+        repository::get_recipe_suggestions(&req.state().pool, query.days, query.limit).await?;
+    Body::from_json(&GetSuggestionsResponse { suggestions })
+}
+
+async fn find_location(req: &Request<AppContext>, name: &str) -> tide::Result<StorageLocation> {
+    get_storage_location(&req.state().pool, name)
+        .await?
+        .ok_or_else(|| {
+            tide::Error::from_str(
+                StatusCode::BadRequest,
+                format!("Storage location '{}' does not exist", name),
+            )
+        })
+}
+
+fn ensure_positive(quantity: Option<f64>) -> tide::Result<()> {
+    match quantity {
+        Some(quantity) if quantity <= 0.0 => Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Quantity must be a positive number",
+        )),
+        _ => Ok(()),
+    }
+}
+
 async fn create_food(mut req: Request<AppContext>) -> tide::Result<Response> {
     let new_food: NewFood = req.body_json().await?;
+    ensure_positive(new_food.quantity)?;
+    if let Some(location) = &new_food.location {
+        find_location(&req, location).await?;
+    }
     let created_food = repository::create_food(&req.state().pool, new_food).await?;
 
     let body = Body::from_json(&created_food)?;
     Ok(Response::builder(StatusCode::Created).body(body).build())
 }
 
+async fn replace_food(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let food_id = parse_param(&req, "id")?;
+    let food: NewFood = req.body_json().await?;
+    save_food(&req, food_id, food.into()).await
+}
+
+async fn update_food(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let food_id = parse_param(&req, "id")?;
+    let patch: FoodPatch = req.body_json().await?;
+    save_food(&req, food_id, patch).await
+}
+
+async fn save_food(
+    req: &Request<AppContext>,
+    food_id: i32,
+    patch: FoodPatch,
+) -> tide::Result<Response> {
+    ensure_positive(patch.quantity.flatten())?;
+    if let Some(location) = &patch.location {
+        find_location(req, location).await?;
+    }
+
+    let res = match repository::update_food(&req.state().pool, food_id, patch).await? {
+        Some(food) => Body::from_json(&food)?.into(),
+        None => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+struct ConsumeBody {
+    quantity: f64,
+}
+
+/// Uses up some of a food, responding with what is left or 204 once none is.
+async fn consume_food(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let food_id = parse_param(&req, "id")?;
+    let body: ConsumeBody = req.body_json().await?;
+    ensure_positive(Some(body.quantity))?;
+
+    let res = match repository::consume_food(&req.state().pool, food_id, body.quantity).await? {
+        None => StatusCode::NotFound.into(),
+        Some(Consumed::Remaining(food)) => Body::from_json(&food)?.into(),
+        Some(Consumed::UsedUp) => StatusCode::NoContent.into(),
+        Some(Consumed::NoQuantity) => {
+            return Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                format!("Food '{}' has no quantity to consume from", food_id),
+            ))
+        }
+    };
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MoveBody {
+    location: String,
+    /// Extends the best before date by the days the location adds.
+    #[serde(default)]
+    extend_expiry: bool,
+}
+
+async fn move_food(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let food_id = parse_param(&req, "id")?;
+    let body: MoveBody = req.body_json().await?;
+    let location = find_location(&req, &body.location).await?;
+    if body.extend_expiry && location.expiry_extension_days.is_none() {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!(
+                "Storage location '{}' does not extend expiry",
+                location.name
+            ),
+        ));
+    }
+
+    let moved_food =
+        repository::move_food(&req.state().pool, food_id, &location, body.extend_expiry).await?;
+    let res = match moved_food {
+        Some(food) => Body::from_json(&food)?.into(),
+        None => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+struct DeleteFoodQuery {
+    #[serde(default)]
+    reason: Option<RemovalReason>,
+}
+
 async fn delete_food(req: Request<AppContext>) -> tide::Result<Response> {
     let food_id = parse_param(&req, "id")?;
-    repository::delete_food(&req.state().pool, food_id).await?;
+    let query: DeleteFoodQuery = req.query()?;
+    let reason = query.reason.ok_or_else(|| {
+        tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Query param 'reason' is required: consumed, thrownAway or givenAway",
+        )
+    })?;
+    repository::delete_food(&req.state().pool, food_id, reason).await?;
     Ok(Response::new(StatusCode::NoContent))
 }
@@ -1,16 +1,30 @@
-use crate::domain::{Food, NewFood};
-use sqlx::{Executor, Postgres};
+use std::collections::HashMap;
+
+use crate::domain::{
+    ExpiringFood, Food, FoodPatch, NewFood, RecipeSuggestion, RecipeSummary, StorageLocation,
+};
+use serde::Deserialize;
+use sqlx::{Acquire, Executor, Postgres};
+use time::Date;
+use uuid::Uuid;
 
 pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> {}
 impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> {}
 
-pub async fn get_foods<'a, E: PgExecutor<'a>>(exec: E, limit: i32) -> anyhow::Result<Vec<Food>> {
+/// Foods expiring first come first, from every location when `location` is `None`.
+pub async fn get_foods<'a, E: PgExecutor<'a>>(
+    exec: E,
+    location: Option<&str>,
+    limit: i32,
+) -> anyhow::Result<Vec<Food>> {
     let foods = sqlx::query_as(
         "SELECT *
          FROM foods
-         ORDER BY best_before_date ASC
-         LIMIT $1",
+         WHERE $1::text IS NULL OR location = $1
+         ORDER BY coalesce(extended_best_before_date, best_before_date) ASC, id ASC
+         LIMIT $2",
     )
+    .bind(location)
     .bind(limit)
     .fetch_all(exec)
     .await?;
@@ -18,25 +32,339 @@ pub async fn get_foods<'a, E: PgExecutor<'a>>(exec: E, limit: i32) -> anyhow::Re
     Ok(foods)
 }
 
+/// Foods expiring within `within_days` from today, already expired ones included.
+pub async fn get_expiring_foods<'a, E: PgExecutor<'a>>(
+    exec: E,
+    within_days: i32,
+) -> anyhow::Result<Vec<Food>> {
+    let foods = sqlx::query_as(
+        "SELECT *
+         FROM foods
+         WHERE coalesce(extended_best_before_date, best_before_date) <= current_date + $1::integer
+         ORDER BY coalesce(extended_best_before_date, best_before_date) ASC, id ASC",
+    )
+    .bind(within_days)
+    .fetch_all(exec)
+    .await?;
+
+    Ok(foods)
+}
+
+/// Names are matched trimmed and lowercased, `names` should be too.
+pub async fn get_foods_by_names<'a, E: PgExecutor<'a>>(
+    exec: E,
+    names: &[String],
+) -> anyhow::Result<Vec<Food>> {
+    let foods = sqlx::query_as(
+        "SELECT *
+         FROM foods
+         WHERE lower(trim(name)) = ANY($1)
+         ORDER BY best_before_date ASC",
+    )
+    .bind(names)
+    .fetch_all(exec)
+    .await?;
+
+    Ok(foods)
+}
+
+// How much of a food name has to be found in an ingredient or a recipe body.
+const FOOD_MATCH_THRESHOLD: f32 = 0.6;
+
+// Foods expiring between today and $1 days from now, with the recipes using them.
+// Ingredients are matched when a recipe lists some, its body otherwise. Binds $1 and $2.
+const EXPIRING_FOOD_MATCHES: &str = "
+    WITH expiring AS (
+        SELECT id, name, coalesce(extended_best_before_date, best_before_date) AS expires_on
+        FROM foods
+        WHERE coalesce(extended_best_before_date, best_before_date)
+          BETWEEN current_date AND current_date + $1::integer
+    ), matches AS (
+        SELECT r.id AS recipe_id, r.name AS recipe_name, r.quick AS recipe_quick,
+          f.id AS food_id, f.name AS food_name, f.expires_on
+        FROM recipes r
+        CROSS JOIN expiring f
+        WHERE r.archived_at IS NULL
+          AND CASE
+            WHEN EXISTS (SELECT 1 FROM recipe_ingredients i WHERE i.recipe_id = r.id)
+            THEN EXISTS (
+              SELECT 1 FROM recipe_ingredients i
+              WHERE i.recipe_id = r.id
+                AND word_similarity(lower(f.name), lower(i.name)) >= $2
+            )
+            ELSE word_similarity(lower(f.name), lower(r.body_plain_text)) >= $2
+          END
+    )";
+
+#[derive(sqlx::FromRow)]
+struct ExpiringFoodMatchDb {
+    recipe_id: Uuid,
+    recipe_name: String,
+    recipe_quick: bool,
+    food_id: i32,
+    food_name: String,
+    expires_on: Date,
+}
+
+/// Recipes using the most foods expiring within `within_days`, then the soonest to expire.
+pub async fn get_recipe_suggestions<'a, E: PgExecutor<'a>>(
+    exec: E,
+    within_days: i32,
+    limit: usize,
+) -> anyhow::Result<Vec<RecipeSuggestion>> {
+    let rows: Vec<ExpiringFoodMatchDb> = sqlx::query_as(&format!(
+        "{} SELECT * FROM matches ORDER BY recipe_id, expires_on, food_id",
+        EXPIRING_FOOD_MATCHES
+    ))
+    .bind(within_days)
+    .bind(FOOD_MATCH_THRESHOLD)
+    .fetch_all(exec)
+    .await?;
+
+    let mut suggestions: Vec<RecipeSuggestion> = Vec::new();
+    for row in rows {
+        let food = ExpiringFood {
+            id: row.food_id,
+            name: row.food_name,
+            expires_on: row.expires_on,
+        };
+        match suggestions.last_mut() {
+            Some(suggestion) if suggestion.recipe.id == row.recipe_id => {
+                suggestion.foods.push(food)
+            }
+            _ => suggestions.push(RecipeSuggestion {
+                recipe: RecipeSummary {
+                    id: row.recipe_id,
+                    name: row.recipe_name,
+                    quick: row.recipe_quick,
+                },
+                foods: vec![food],
+            }),
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.foods
+            .len()
+            .cmp(&a.foods.len())
+            .then(a.foods[0].expires_on.cmp(&b.foods[0].expires_on))
+            .then(a.recipe.name.cmp(&b.recipe.name))
+    });
+    suggestions.truncate(limit);
+
+    Ok(suggestions)
+}
+
+/// How many foods expiring within `within_days` each recipe uses, missing when none.
+pub async fn get_expiring_food_counts<'a, E: PgExecutor<'a>>(
+    exec: E,
+    within_days: i32,
+) -> anyhow::Result<HashMap<Uuid, i64>> {
+    let counts: Vec<(Uuid, i64)> = sqlx::query_as(&format!(
+        "{} SELECT recipe_id, count(*) FROM matches GROUP BY recipe_id",
+        EXPIRING_FOOD_MATCHES
+    ))
+    .bind(within_days)
+    .bind(FOOD_MATCH_THRESHOLD)
+    .fetch_all(exec)
+    .await?;
+
+    Ok(counts.into_iter().collect())
+}
+
 pub async fn create_food<'a, E: PgExecutor<'a>>(exec: E, food: NewFood) -> anyhow::Result<Food> {
     let created_food = sqlx::query_as(
-        "INSERT INTO foods ( name, best_before_date )
-         VALUES ( $1, $2 )
+        "INSERT INTO foods ( name, best_before_date, quantity, unit, location )
+         VALUES (
+           $1, $2, $3, $4,
+           coalesce($5, (SELECT name FROM storage_locations ORDER BY position, name LIMIT 1))
+         )
          RETURNING *",
     )
     .bind(&food.name)
     .bind(&food.best_before_date)
+    .bind(food.quantity)
+    .bind(&food.unit)
+    .bind(&food.location)
     .fetch_one(exec)
     .await?;
 
     Ok(created_food)
 }
 
-pub async fn delete_food<'a, E: PgExecutor<'a>>(exec: E, id: i32) -> anyhow::Result<()> {
-    sqlx::query("DELETE FROM foods WHERE id = $1")
+pub async fn update_food<'a, E: PgExecutor<'a>>(
+    exec: E,
+    id: i32,
+    patch: FoodPatch,
+) -> anyhow::Result<Option<Food>> {
+    let updated_food = sqlx::query_as(
+        "UPDATE foods
+         SET name = coalesce($2, name),
+             best_before_date = coalesce($3, best_before_date),
+             quantity = CASE WHEN $4 THEN $5 ELSE quantity END,
+             unit = CASE WHEN $6 THEN $7 ELSE unit END,
+             location = coalesce($8, location),
+             extended_best_before_date = CASE
+               WHEN $3 <> best_before_date THEN NULL
+               WHEN $8 <> location AND NOT EXISTS (
+                 SELECT 1 FROM storage_locations
+                 WHERE name = $8 AND expiry_extension_days IS NOT NULL
+               ) THEN NULL
+               ELSE extended_best_before_date
+             END
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(&patch.name)
+    .bind(patch.best_before_date)
+    .bind(patch.quantity.is_some())
+    .bind(patch.quantity.flatten())
+    .bind(patch.unit.is_some())
+    .bind(patch.unit.flatten())
+    .bind(&patch.location)
+    .fetch_optional(exec)
+    .await?;
+
+    Ok(updated_food)
+}
+
+/// Moves a food to `location`, extending its best before date by the days
+/// that location adds when `extend_expiry` is set. The extension is dropped
+/// when moving somewhere that doesn't extend expiry, like back to the fridge.
+pub async fn move_food<'a, E: PgExecutor<'a>>(
+    exec: E,
+    id: i32,
+    location: &StorageLocation,
+    extend_expiry: bool,
+) -> anyhow::Result<Option<Food>> {
+    let extension_days = location.expiry_extension_days.filter(|_| extend_expiry);
+    let moved_food = sqlx::query_as(
+        "UPDATE foods
+         SET location = $2,
+             extended_best_before_date = CASE
+               WHEN $3::integer IS NOT NULL THEN best_before_date + $3::integer
+               WHEN $4 THEN NULL
+               ELSE extended_best_before_date
+             END
+         WHERE id = $1
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(&location.name)
+    .bind(extension_days)
+    .bind(location.expiry_extension_days.is_none())
+    .fetch_optional(exec)
+    .await?;
+
+    Ok(moved_food)
+}
+
+/// Why a food left, kept in the food events log.
+/// Written in camelCase in the API like other enums (`thrownAway`),
+/// stored in snake_case in `food_events` (`thrown_away`).
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RemovalReason {
+    Consumed,
+    ThrownAway,
+    GivenAway,
+}
+
+impl RemovalReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            RemovalReason::Consumed => "consumed",
+            RemovalReason::ThrownAway => "thrown_away",
+            RemovalReason::GivenAway => "given_away",
+        }
+    }
+}
+
+pub enum Consumed {
+    /// Some of the food is left.
+    Remaining(Food),
+    /// None is left, the food was deleted.
+    UsedUp,
+    /// Nothing to take the amount from.
+    NoQuantity,
+}
+
+/// Takes `amount` off the quantity of a food, deleting it once there is none left.
+/// What was taken is logged as consumed.
+pub async fn consume_food<'a, E>(exec: E, id: i32, amount: f64) -> anyhow::Result<Option<Consumed>>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+{
+    let mut tx = exec.begin().await?;
+
+    let food: Option<Food> = sqlx::query_as("SELECT * FROM foods WHERE id = $1 FOR UPDATE")
         .bind(id)
-        .execute(exec)
+        .fetch_optional(&mut *tx)
         .await?;
+    let quantity = match food {
+        None => return Ok(None),
+        Some(Food { quantity: None, .. }) => return Ok(Some(Consumed::NoQuantity)),
+        Some(Food {
+            quantity: Some(quantity),
+            ..
+        }) => quantity,
+    };
+
+    sqlx::query(
+        "INSERT INTO food_events (food_id, name, reason, quantity, unit, location, expires_on)
+         SELECT id, name, $2, $3, unit, location,
+           coalesce(extended_best_before_date, best_before_date)
+         FROM foods
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(RemovalReason::Consumed.as_str())
+    .bind(amount.min(quantity))
+    .execute(&mut *tx)
+    .await?;
+
+    // Rounded so float noise doesn't leave a sliver of food behind.
+    let remaining = ((quantity - amount) * 1e6).round() / 1e6;
+    let consumed = if remaining > 0.0 {
+        let food = sqlx::query_as("UPDATE foods SET quantity = $2 WHERE id = $1 RETURNING *")
+            .bind(id)
+            .bind(remaining)
+            .fetch_one(&mut *tx)
+            .await?;
+        Consumed::Remaining(food)
+    } else {
+        sqlx::query("DELETE FROM foods WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        Consumed::UsedUp
+    };
+
+    tx.commit().await?;
+
+    Ok(Some(consumed))
+}
+
+/// Logs why the food left along with deleting it.
+pub async fn delete_food<'a, E: PgExecutor<'a>>(
+    exec: E,
+    id: i32,
+    reason: RemovalReason,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "WITH deleted AS (
+           DELETE FROM foods WHERE id = $1 RETURNING *
+         )
+         INSERT INTO food_events (food_id, name, reason, quantity, unit, location, expires_on)
+         SELECT id, name, $2, quantity, unit, location,
+           coalesce(extended_best_before_date, best_before_date)
+         FROM deleted",
+    )
+    .bind(id)
+    .bind(reason.as_str())
+    .execute(exec)
+    .await?;
 
     Ok(())
 }
@@ -11,8 +11,66 @@ use lol_html::{
 lazy_static! {
     static ref ELEMENTS_TO_BE_REMOVED_COMPLETELY: HashSet<&'static str> =
         HashSet::from(["head", "style", "script"]);
+    static ref UNSAFE_ELEMENTS: HashSet<&'static str> = HashSet::from([
+        "embed",
+        "frame",
+        "frameset",
+        "head",
+        "iframe",
+        "math",
+        "noembed",
+        "noframes",
+        "noscript",
+        "object",
+        "plaintext",
+        "script",
+        "select",
+        "style",
+        "svg",
+        "template",
+        "textarea",
+        "title",
+        "xmp",
+    ]);
+    static ref ALLOWED_ELEMENTS: HashSet<&'static str> = HashSet::from([
+        "a",
+        "b",
+        "blockquote",
+        "br",
+        "code",
+        "del",
+        "em",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "hr",
+        "i",
+        "img",
+        "li",
+        "ol",
+        "p",
+        "pre",
+        "s",
+        "strong",
+        "sub",
+        "sup",
+        "table",
+        "tbody",
+        "td",
+        "th",
+        "thead",
+        "tr",
+        "u",
+        "ul",
+    ]);
 }
 
+const URL_ATTRIBUTES: [&str; 2] = ["href", "src"];
+const SAFE_URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
 fn extract_text(el: &mut Element) -> Result<(), Box<dyn Error + Send + Sync>> {
     if ELEMENTS_TO_BE_REMOVED_COMPLETELY.contains(el.tag_name().as_str()) {
         el.remove();
@@ -32,6 +90,126 @@ fn remove_doctype(d: &mut Doctype) -> Result<(), Box<dyn Error + Send + Sync>> {
     Ok(())
 }
 
+fn allowed_attributes(tag_name: &str) -> &'static [&'static str] {
+    match tag_name {
+        "a" => &["href", "title"],
+        "img" => &["src", "alt", "title", "width", "height"],
+        "ol" => &["start"],
+        "td" | "th" => &["colspan", "rowspan"],
+        _ => &[],
+    }
+}
+
+/// Relative URLs and a few schemes are safe, anything else could run code.
+fn is_safe_url(url: &str) -> bool {
+    let url: String = decode_entities(url)
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let scheme_end = url.find([':', '/', '?', '#']);
+    let scheme = &url[..scheme_end.unwrap_or(url.len())];
+
+    // Browsers also decode entities missing their `;`, refuse any left.
+    if scheme.contains('&') {
+        return false;
+    }
+    match scheme_end {
+        Some(end) if url[end..].starts_with(':') => {
+            SAFE_URL_SCHEMES.contains(&scheme.to_lowercase().as_str())
+        }
+        _ => true,
+    }
+}
+
+fn sanitize_element(el: &mut Element) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tag_name = el.tag_name();
+    if UNSAFE_ELEMENTS.contains(tag_name.as_str()) {
+        el.remove();
+        return Ok(());
+    }
+    if !ALLOWED_ELEMENTS.contains(tag_name.as_str()) {
+        el.remove_and_keep_content();
+        return Ok(());
+    }
+
+    let allowed = allowed_attributes(&tag_name);
+    let attributes: Vec<(String, String)> = el
+        .attributes()
+        .iter()
+        .map(|a| (a.name(), a.value()))
+        .collect();
+    for (name, value) in attributes {
+        if !allowed.contains(&name.as_str()) {
+            el.remove_attribute(&name);
+        } else if URL_ATTRIBUTES.contains(&name.as_str()) && !is_safe_url(&value) {
+            el.set_attribute(&name, "#")?;
+        }
+    }
+    Ok(())
+}
+
+/// Keeps only allow-listed elements and attributes, replacing unsafe URLs with `#`.
+pub fn sanitize_html(s: &str) -> Result<String, RewritingError> {
+    rewrite_str(
+        s,
+        RewriteStrSettings {
+            element_content_handlers: vec![element!("*", sanitize_element)],
+            document_content_handlers: vec![
+                doc_comments!(remove_comments),
+                doctype!(remove_doctype),
+            ],
+            ..Default::default()
+        },
+    )
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = match entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => entity.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Decodes the common named and all numeric character references.
+pub fn decode_entities(s: &str) -> String {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded_entity = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match decoded_entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
 pub fn to_plain_text(s: &str) -> Result<String, RewritingError> {
     let rewriten_str = rewrite_str(
         s,
@@ -97,4 +275,61 @@ And here a link in a paragraph...
 
         Ok(())
     }
+
+    #[test]
+    fn sanitize_html_keeps_allowed_markup() -> anyhow::Result<()> {
+        let html = r###"<h2>Title</h2><p>Some <strong>bold</strong> <a href="https://example.com" title="Ex">link</a></p><ol start="2"><li>Step</li></ol>"###;
+
+        let result = sanitize_html(html)?;
+
+        assert_eq!(result, html);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_html_removes_disallowed_elements_and_attributes() -> anyhow::Result<()> {
+        let html = r###"<!-- comment --><div class="x"><p onclick="steal()" style="color: red">Text</p><script>steal()</script><iframe src="https://evil.example.com"></iframe><span>kept</span></div>"###;
+        let expected = r###"<p>Text</p>kept"###;
+
+        let result = sanitize_html(html)?;
+
+        assert_eq!(result, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_html_removes_raw_text_elements_with_their_content() -> anyhow::Result<()> {
+        for tag in ["xmp", "noembed", "noframes", "plaintext"] {
+            let html = format!(
+                r###"<p>Text</p><{tag}><img src=x onerror=alert(1)></{tag}>"###,
+                tag = tag
+            );
+
+            let result = sanitize_html(&html)?;
+
+            assert_eq!(result, "<p>Text</p>", "{}", tag);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_html_rewrites_unsafe_urls() -> anyhow::Result<()> {
+        let html = r###"<a href="javascript:steal()">a</a><a href=" JaVaScRiPt:steal()">b</a><a href="java&#x09;script&#58;steal()">c</a><a href="javascript&#58steal()">d</a><img src="data:image/svg+xml;base64,PHN2Zz4=" alt="e"><a href="/recipes/1?a=1&amp;b=2">f</a><a href="mailto:me@example.com">g</a>"###;
+        let expected = r###"<a href="#">a</a><a href="#">b</a><a href="#">c</a><a href="#">d</a><img src="#" alt="e"><a href="/recipes/1?a=1&amp;b=2">f</a><a href="mailto:me@example.com">g</a>"###;
+
+        let result = sanitize_html(html)?;
+
+        assert_eq!(result, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_entities_decodes_named_and_numeric_references() {
+        assert_eq!(decode_entities("Mom&#39;s &amp; &#x41;&lt;"), "Mom's & A<");
+        assert_eq!(decode_entities("R&D &unknown; &#58"), "R&D &unknown; &#58");
+    }
 }
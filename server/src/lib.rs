@@ -2,8 +2,14 @@ mod days;
 mod domain;
 mod foods;
 mod html_filter;
+mod meal_slots;
+pub mod notifications;
 mod recipes;
+mod shopping_list;
+mod stats;
+mod storage_locations;
 mod tide_utils;
+mod units;
 
 use sqlx::postgres::PgPool;
 use tide::Server;
@@ -20,10 +26,19 @@ pub fn init_app(pool: PgPool) -> Server<AppContext> {
     days::handlers::init(&mut app);
     recipes::handlers::init(&mut app);
     foods::handlers::init(&mut app);
+    meal_slots::handlers::init(&mut app);
+    shopping_list::handlers::init(&mut app);
+    stats::handlers::init(&mut app);
+    storage_locations::handlers::init(&mut app);
 
     app
 }
 
+/// One-off cleanup of recipe bodies saved before they were sanitized on write.
+pub async fn sanitize_recipes(pool: &PgPool) -> anyhow::Result<u64> {
+    recipes::repository::sanitize_recipe_bodies(pool).await
+}
+
 format_description!(
     serde_iso_date,
     Date,
@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
-use slice_n_dice_server::init_app;
+use slice_n_dice_server::notifications::{notify_daily, Notifier, SmtpNotifier, WebhookNotifier};
+use slice_n_dice_server::{init_app, sanitize_recipes};
 use sqlx::postgres::PgPool;
 use std::env;
+use time::macros::{format_description, time};
+use time::Time;
 
 #[async_std::main]
 async fn main() -> Result<()> {
@@ -13,9 +16,50 @@ async fn main() -> Result<()> {
 
     sqlx::migrate!().run(&pool).await?;
 
+    if env::args().nth(1).as_deref() == Some("sanitize-recipes") {
+        let sanitized_count = sanitize_recipes(&pool).await?;
+        println!("Sanitized {} recipe(s)", sanitized_count);
+        return Ok(());
+    }
+
+    let notifiers = expiry_notifiers()?;
+    if !notifiers.is_empty() {
+        let within_days = match env::var("EXPIRY_WITHIN_DAYS") {
+            Ok(days) => days
+                .parse()
+                .context("EXPIRY_WITHIN_DAYS should be a number")?,
+            Err(_) => 3,
+        };
+        let at = match env::var("EXPIRY_NOTIFY_AT") {
+            Ok(at) => Time::parse(&at, format_description!("[hour]:[minute]"))
+                .context("EXPIRY_NOTIFY_AT should be a UTC time like 08:00")?,
+            Err(_) => time!(08:00),
+        };
+        async_std::task::spawn(notify_daily(pool.clone(), at, within_days, notifiers));
+    }
+
     let app = init_app(pool);
 
     app.listen("127.0.0.1:8091").await?;
 
     Ok(())
 }
+
+/// Daily expiry digests are only sent when a webhook or SMTP relay is set up.
+fn expiry_notifiers() -> Result<Vec<Box<dyn Notifier>>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+
+    if let Ok(url) = env::var("EXPIRY_WEBHOOK_URL") {
+        notifiers.push(Box::new(WebhookNotifier::new(&url)?));
+    }
+
+    if let Ok(url) = env::var("EXPIRY_SMTP_URL") {
+        let from = env::var("EXPIRY_EMAIL_FROM")
+            .context("EXPIRY_EMAIL_FROM env variable needs to be set to send emails")?;
+        let to = env::var("EXPIRY_EMAIL_TO")
+            .context("EXPIRY_EMAIL_TO env variable needs to be set to send emails")?;
+        notifiers.push(Box::new(SmtpNotifier::new(&url, &from, &to)?));
+    }
+
+    Ok(notifiers)
+}
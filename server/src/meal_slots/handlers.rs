@@ -0,0 +1,93 @@
+use serde::Serialize;
+use tide::{Body, Request, Response, Server, StatusCode};
+
+use crate::domain::MealSlot;
+use crate::meal_slots::repository::{self, SlotDeletion, SlotUpdate, BUILT_IN_SLOTS};
+use crate::AppContext;
+
+pub fn init(app: &mut Server<AppContext>) {
+    let mut meal_slots_api = app.at("/api/v0/meal-slots");
+    meal_slots_api.get(get_meal_slots);
+    meal_slots_api.post(create_meal_slot);
+    meal_slots_api.at("/:name").put(update_meal_slot);
+    meal_slots_api.at("/:name").delete(delete_meal_slot);
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GetMealSlotsResponse {
+    meal_slots: Vec<MealSlot>,
+}
+
+async fn get_meal_slots(req: Request<AppContext>) -> tide::Result<Body> {
+    let meal_slots = repository::get_meal_slots(&req.state().pool).await?;
+    Body::from_json(&GetMealSlotsResponse { meal_slots })
+}
+
+// Segments of the v1 day routes that would shadow a slot of the same name.
+const RESERVED_NAMES: [&str; 1] = ["randomize"];
+
+fn ensure_valid_name(slot: &MealSlot) -> tide::Result<()> {
+    if slot.name.is_empty()
+        || slot.name.contains('/')
+        || RESERVED_NAMES.contains(&slot.name.as_str())
+    {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!("'{}' can't be used as a meal slot name", slot.name),
+        ));
+    }
+    Ok(())
+}
+
+fn ensure_not_built_in(name: &str) -> tide::Result<()> {
+    if BUILT_IN_SLOTS.contains(&name) {
+        return Err(tide::Error::from_str(
+            StatusCode::Conflict,
+            format!("Meal slot '{}' is built in", name),
+        ));
+    }
+    Ok(())
+}
+
+async fn create_meal_slot(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let new_slot: MealSlot = req.body_json().await?;
+    ensure_valid_name(&new_slot)?;
+    let res = match repository::create_meal_slot(&req.state().pool, new_slot).await? {
+        Some(slot) => Response::builder(StatusCode::Created)
+            .body(Body::from_json(&slot)?)
+            .build(),
+        None => StatusCode::Conflict.into(),
+    };
+    Ok(res)
+}
+
+async fn update_meal_slot(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let slot_data: MealSlot = req.body_json().await?;
+    ensure_valid_name(&slot_data)?;
+    let name = req.param("name")?;
+    // Built in slots can still be moved around or made not randomizable.
+    if slot_data.name != name {
+        ensure_not_built_in(name)?;
+    }
+    let res = match repository::update_meal_slot(&req.state().pool, name, slot_data).await? {
+        Some(SlotUpdate::Updated(slot)) => Body::from_json(&slot)?.into(),
+        Some(SlotUpdate::NameTaken) => StatusCode::Conflict.into(),
+        None => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+/// Meals planned in a slot have to be cleared before it can be deleted.
+async fn delete_meal_slot(req: Request<AppContext>) -> tide::Result<Response> {
+    let name = req.param("name")?;
+    ensure_not_built_in(name)?;
+    match repository::delete_meal_slot(&req.state().pool, name).await? {
+        Some(SlotDeletion::Deleted) => Ok(StatusCode::NoContent.into()),
+        Some(SlotDeletion::HasMeals) => Err(tide::Error::from_str(
+            StatusCode::Conflict,
+            format!("Meal slot '{}' still has planned meals", name),
+        )),
+        None => Ok(StatusCode::NotFound.into()),
+    }
+}
@@ -0,0 +1,136 @@
+use sqlx::{Acquire, Executor, Postgres};
+
+use crate::domain::MealSlot;
+
+pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> + Clone {}
+impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> + Clone {}
+
+pub const LUNCH: &str = "lunch";
+pub const DINNER: &str = "dinner";
+/// The v0 day routes are made of these, so they can't be renamed or deleted.
+pub const BUILT_IN_SLOTS: [&str; 2] = [LUNCH, DINNER];
+
+pub async fn get_meal_slots<'a, E: PgExecutor<'a>>(exec: E) -> anyhow::Result<Vec<MealSlot>> {
+    let slots = sqlx::query_as(
+        "SELECT *
+         FROM meal_slots
+         ORDER BY position ASC, name ASC",
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(slots)
+}
+
+pub async fn get_meal_slot<'a, E: PgExecutor<'a>>(
+    exec: E,
+    name: &str,
+) -> anyhow::Result<Option<MealSlot>> {
+    let slot = sqlx::query_as(
+        "SELECT *
+         FROM meal_slots
+         WHERE name = $1",
+    )
+    .bind(name)
+    .fetch_optional(exec)
+    .await?;
+
+    Ok(slot)
+}
+
+/// Returns `None` when a slot with the same name already exists.
+pub async fn create_meal_slot<'a, E: PgExecutor<'a>>(
+    exec: E,
+    slot: MealSlot,
+) -> anyhow::Result<Option<MealSlot>> {
+    let created_slot = sqlx::query_as(
+        "INSERT INTO meal_slots ( name, position, randomizable )
+         VALUES ( $1, $2, $3 )
+         ON CONFLICT (name) DO NOTHING
+         RETURNING *",
+    )
+    .bind(&slot.name)
+    .bind(slot.position)
+    .bind(slot.randomizable)
+    .fetch_optional(exec)
+    .await?;
+
+    Ok(created_slot)
+}
+
+pub enum SlotUpdate {
+    Updated(MealSlot),
+    /// Another slot already has the new name.
+    NameTaken,
+}
+
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Renaming a slot carries its planned meals over.
+pub async fn update_meal_slot<'a, E: PgExecutor<'a>>(
+    exec: E,
+    name: &str,
+    slot: MealSlot,
+) -> anyhow::Result<Option<SlotUpdate>> {
+    let updated_slot = sqlx::query_as(
+        "UPDATE meal_slots
+         SET name = $2, position = $3, randomizable = $4
+         WHERE name = $1
+         RETURNING *",
+    )
+    .bind(name)
+    .bind(&slot.name)
+    .bind(slot.position)
+    .bind(slot.randomizable)
+    .fetch_optional(exec)
+    .await;
+
+    match updated_slot {
+        Ok(updated_slot) => Ok(updated_slot.map(SlotUpdate::Updated)),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+            Ok(Some(SlotUpdate::NameTaken))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub enum SlotDeletion {
+    Deleted,
+    /// Meals are planned in it, deleting it would erase them.
+    HasMeals,
+}
+
+pub async fn delete_meal_slot<'a, E>(exec: E, name: &str) -> anyhow::Result<Option<SlotDeletion>>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+{
+    let mut tx = exec.begin().await?;
+
+    // Locked so no meal can be planned in it until it is gone.
+    let found: Option<String> =
+        sqlx::query_scalar("SELECT name FROM meal_slots WHERE name = $1 FOR UPDATE")
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if found.is_none() {
+        return Ok(None);
+    }
+
+    let has_meals: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM planned_meals WHERE slot = $1)")
+            .bind(name)
+            .fetch_one(&mut *tx)
+            .await?;
+    if has_meals {
+        return Ok(Some(SlotDeletion::HasMeals));
+    }
+
+    sqlx::query("DELETE FROM meal_slots WHERE name = $1")
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(SlotDeletion::Deleted))
+}
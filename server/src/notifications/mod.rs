@@ -0,0 +1,149 @@
+//! Lets us know about foods about to go off without opening the fridge page.
+
+mod smtp;
+mod webhook;
+
+use std::time::Duration;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{Date, OffsetDateTime, Time};
+
+use crate::domain::Food;
+use crate::foods::repository::get_expiring_foods;
+use crate::serde_iso_date;
+
+pub use smtp::SmtpNotifier;
+pub use webhook::WebhookNotifier;
+
+/// The foods expiring soon on a given day.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiryDigest {
+    #[serde(with = "serde_iso_date")]
+    pub date: Date,
+    pub within_days: i32,
+    /// Soonest to expire first, already expired ones included.
+    pub foods: Vec<Food>,
+}
+
+impl ExpiryDigest {
+    pub fn subject(&self) -> String {
+        match self.foods.len() {
+            1 => String::from("1 food about to expire"),
+            count => format!("{} foods about to expire", count),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!("Foods expiring within {} days:\n", self.within_days);
+        for food in &self.foods {
+            let expires_on = food
+                .extended_best_before_date
+                .unwrap_or(food.best_before_date);
+            let verb = if expires_on < self.date {
+                "expired"
+            } else {
+                "expires"
+            };
+            text.push_str(&format!(
+                "- {} ({}): {} on {}\n",
+                food.name, food.location, verb, expires_on
+            ));
+        }
+        text
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, digest: &ExpiryDigest) -> anyhow::Result<()>;
+}
+
+/// Sends a digest through every notifier, unless nothing is about to expire.
+/// Returns whether one was sent, or an error once every notifier was tried if any failed.
+pub async fn notify_expiring_foods(
+    pool: &PgPool,
+    within_days: i32,
+    notifiers: &[Box<dyn Notifier>],
+) -> anyhow::Result<bool> {
+    let foods = get_expiring_foods(pool, within_days).await?;
+    if foods.is_empty() {
+        return Ok(false);
+    }
+
+    let digest = ExpiryDigest {
+        date: OffsetDateTime::now_utc().date(),
+        within_days,
+        foods,
+    };
+    // One notifier being down shouldn't keep the others from sending.
+    let mut failures = 0;
+    for notifier in notifiers {
+        if let Err(err) = notifier.notify(&digest).await {
+            tide::log::error!("Could not send expiry notification: {:?}", err);
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        bail!(
+            "{} of {} expiry notifiers failed",
+            failures,
+            notifiers.len()
+        );
+    }
+
+    Ok(true)
+}
+
+/// Sends a digest every day at `at` UTC, failures are logged and retried the day after.
+pub async fn notify_daily(
+    pool: PgPool,
+    at: Time,
+    within_days: i32,
+    notifiers: Vec<Box<dyn Notifier>>,
+) {
+    loop {
+        async_std::task::sleep(time_until(OffsetDateTime::now_utc(), at)).await;
+        if let Err(err) = notify_expiring_foods(&pool, within_days, &notifiers).await {
+            tide::log::error!("Could not send expiry notifications: {:?}", err);
+        }
+    }
+}
+
+fn time_until(now: OffsetDateTime, at: Time) -> Duration {
+    let mut next = now.replace_time(at);
+    if next <= now {
+        next += time::Duration::DAY;
+    }
+    (next - now).try_into().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{datetime, time};
+
+    use super::*;
+
+    #[test]
+    fn time_until_waits_for_later_today() {
+        let now = datetime!(2022-01-10 06:30 UTC);
+        assert_eq!(time_until(now, time!(08:00)), Duration::from_secs(90 * 60));
+    }
+
+    #[test]
+    fn time_until_waits_for_tomorrow_once_past() {
+        let now = datetime!(2022-01-10 08:00 UTC);
+        assert_eq!(
+            time_until(now, time!(08:00)),
+            Duration::from_secs(24 * 60 * 60)
+        );
+        let now = datetime!(2022-01-10 23:00 UTC);
+        assert_eq!(
+            time_until(now, time!(08:00)),
+            Duration::from_secs(9 * 60 * 60)
+        );
+    }
+}
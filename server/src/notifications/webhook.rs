@@ -0,0 +1,34 @@
+use anyhow::bail;
+use async_trait::async_trait;
+use tide::http::Url;
+
+use super::{ExpiryDigest, Notifier};
+
+/// Posts the digest as JSON to a URL.
+pub struct WebhookNotifier {
+    url: Url,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        Ok(WebhookNotifier {
+            url: Url::parse(url)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, digest: &ExpiryDigest) -> anyhow::Result<()> {
+        let res = surf::post(self.url.clone())
+            .body_json(digest)
+            .map_err(surf::Error::into_inner)?
+            .await
+            .map_err(surf::Error::into_inner)?;
+        if !res.status().is_success() {
+            bail!("Webhook responded with {}", res.status());
+        }
+
+        Ok(())
+    }
+}
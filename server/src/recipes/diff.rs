@@ -0,0 +1,141 @@
+//! Line by line diff of the plain text of two versions of a recipe.
+
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "text")]
+pub enum DiffLine {
+    Unchanged(String),
+    Added(String),
+    Removed(String),
+}
+
+// Cells of the comparison table, about 16 MB, enough for changing
+// a couple thousand lines at once.
+const MAX_COMPARED_LINES: usize = 4_000_000;
+
+/// Diffs two texts on their longest common subsequence of lines,
+/// removals come before additions where lines were changed.
+/// Returns `None` when too many lines changed to compare them.
+pub fn diff_lines(old: &str, new: &str) -> Option<Vec<DiffLine>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Lines kept at the start and end are left out of the comparison.
+    let prefix = old.iter().zip(&new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let changed_old = &old[prefix..old.len() - suffix];
+    let changed_new = &new[prefix..new.len() - suffix];
+    if changed_old.len().saturating_mul(changed_new.len()) > MAX_COMPARED_LINES {
+        return None;
+    }
+
+    let mut lines: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|l| DiffLine::Unchanged(l.to_string()))
+        .collect();
+    lines.extend(diff_changed_lines(changed_old, changed_new));
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|l| DiffLine::Unchanged(l.to_string())),
+    );
+
+    Some(lines)
+}
+
+fn diff_changed_lines(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Unchanged(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lines_finds_changed_lines() {
+        let old = "Pancakes\n200 g flour\n2 eggs\nMix and fry";
+        let new = "Pancakes\n250 g flour\n2 eggs\nMix and fry\nServe hot";
+
+        let result = diff_lines(old, new);
+
+        assert_eq!(
+            result.unwrap(),
+            vec![
+                DiffLine::Unchanged("Pancakes".to_string()),
+                DiffLine::Removed("200 g flour".to_string()),
+                DiffLine::Added("250 g flour".to_string()),
+                DiffLine::Unchanged("2 eggs".to_string()),
+                DiffLine::Unchanged("Mix and fry".to_string()),
+                DiffLine::Added("Serve hot".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_lines_handles_empty_texts() {
+        assert_eq!(diff_lines("", ""), Some(vec![]));
+        assert_eq!(
+            diff_lines("", "Toast"),
+            Some(vec![DiffLine::Added("Toast".to_string())])
+        );
+        assert_eq!(
+            diff_lines("Toast", ""),
+            Some(vec![DiffLine::Removed("Toast".to_string())])
+        );
+    }
+
+    #[test]
+    fn diff_lines_only_compares_changed_lines() {
+        let kept: String = (0..5_000).map(|n| format!("Step {}\n", n)).collect();
+        let old = format!("{}Old middle\n{}", kept, kept);
+        let new = format!("{}New middle\n{}", kept, kept);
+
+        let result = diff_lines(&old, &new).unwrap();
+
+        assert_eq!(result.len(), 10_002);
+        assert_eq!(result[5_000], DiffLine::Removed("Old middle".to_string()));
+        assert_eq!(result[5_001], DiffLine::Added("New middle".to_string()));
+    }
+
+    #[test]
+    fn diff_lines_gives_up_on_too_many_changes() {
+        let old: String = (0..3_000).map(|n| format!("Old {}\n", n)).collect();
+        let new: String = (0..3_000).map(|n| format!("New {}\n", n)).collect();
+
+        assert_eq!(diff_lines(&old, &new), None);
+    }
+}
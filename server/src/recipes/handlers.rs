@@ -2,18 +2,33 @@ use serde::{Deserialize, Serialize};
 use tide::{Body, Request, Response, Server, StatusCode};
 use uuid::Uuid;
 
-use crate::domain::{NewRecipe, Recipe};
-use crate::recipes::repository;
-use crate::tide_utils::parse_param;
+use crate::domain::{NewRecipe, Recipe, RecipeRevisionSummary, Tag};
+use crate::recipes::diff::{diff_lines, DiffLine};
+use crate::recipes::repository::{RecipeCursor, RecipeListQuery, RecipeSort};
+use crate::recipes::{import, repository, scaling};
+use crate::tide_utils::{parse_param, split_list};
+use crate::units::{convert_ingredient, UnitSystem};
 use crate::AppContext;
 
 pub fn init(app: &mut Server<AppContext>) {
     let mut recipes_api = app.at("/api/v0/recipes");
     recipes_api.get(get_recipes);
     recipes_api.post(create_recipe);
+    recipes_api.at("/import").post(import_recipe);
     recipes_api.at("/:id").get(get_recipe);
     recipes_api.at("/:id").put(update_recipe);
     recipes_api.at("/:id").delete(delete_recipe);
+    recipes_api.at("/:id/restore").put(restore_recipe);
+    recipes_api.at("/:id/revisions").get(get_revisions);
+    recipes_api.at("/:id/revisions/:revision").get(get_revision);
+    recipes_api
+        .at("/:id/revisions/:revision/restore")
+        .put(restore_revision);
+    recipes_api.at("/:id/diff").get(diff_revisions);
+
+    let mut tags_api = app.at("/api/v0/tags");
+    tags_api.get(get_tags);
+    tags_api.at("/:tag").delete(delete_tag);
 }
 
 #[derive(Serialize)]
@@ -21,6 +36,9 @@ struct LightRecipe {
     id: Uuid,
     name: String,
     quick: bool,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
 }
 
 impl From<&Recipe> for LightRecipe {
@@ -29,51 +47,192 @@ impl From<&Recipe> for LightRecipe {
             id: r.id,
             name: r.name.clone(),
             quick: r.quick,
+            tags: r.tags.clone(),
+            snippet: None,
+        }
+    }
+}
+
+impl From<&repository::RecipeListItem> for LightRecipe {
+    fn from(r: &repository::RecipeListItem) -> Self {
+        LightRecipe {
+            snippet: r.snippet.clone(),
+            ..LightRecipe::from(&r.recipe)
         }
     }
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct GetRecipesResponse {
     recipes: Vec<LightRecipe>,
+    /// Recipes matching the query over every page.
+    total: i64,
+    /// Pass as `cursor` to get the next page, missing on the last one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum SearchMode {
+    /// Fuzzy matching on the recipe name, the default.
+    Name,
+    /// Ranked full-text search on the name and body.
+    FullText,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GetRecipesQuery {
     #[serde(default)]
     search: Option<String>,
     #[serde(default)]
+    search_mode: Option<SearchMode>,
+    #[serde(default)]
     limit: Option<i64>,
     #[serde(default)]
     quick: Option<bool>,
+    /// Comma separated, recipes need every one of them.
+    #[serde(default)]
+    tags: Option<String>,
+    /// Lists deleted recipes instead, so they can be restored.
+    #[serde(default)]
+    archived: bool,
+    /// Relevance when searching, name otherwise.
+    #[serde(default)]
+    sort: Option<RecipeSort>,
+    /// From the previous page, with the same query.
+    #[serde(default)]
+    cursor: Option<String>,
 }
 
 async fn get_recipes(req: Request<AppContext>) -> tide::Result<Body> {
     let query: GetRecipesQuery = req.query()?;
-    let recipes = repository::get_recipes(
+    if matches!(query.limit, Some(limit) if limit <= 0) {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Limit must be a positive number",
+        ));
+    }
+
+    let sort = query.sort.unwrap_or(match query.search {
+        Some(_) => RecipeSort::Relevance,
+        None => RecipeSort::Name,
+    });
+    let after = match query.cursor.as_deref().map(RecipeCursor::decode) {
+        None => None,
+        Some(Ok(cursor)) if cursor.sort == sort => Some(cursor),
+        Some(_) => {
+            return Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                "Cursor is invalid or was made for another sort",
+            ))
+        }
+    };
+
+    let page = repository::get_recipes(
         &req.state().pool,
-        query.search.as_deref(),
-        query.limit,
-        query.quick,
+        &RecipeListQuery {
+            search: query.search,
+            full_text: matches!(query.search_mode, Some(SearchMode::FullText)),
+            quick: query.quick,
+            tags: split_list(query.tags.as_deref()),
+            archived: query.archived,
+            sort,
+            after,
+            limit: query.limit,
+        },
     )
     .await?;
-    let light_recipes = recipes.iter().map(|r| r.into()).collect();
+
     Body::from_json(&GetRecipesResponse {
-        recipes: light_recipes,
+        recipes: page.recipes.iter().map(|r| r.into()).collect(),
+        total: page.total,
+        next_cursor: page.next.as_ref().map(RecipeCursor::encode).transpose()?,
     })
 }
 
+#[derive(Deserialize)]
+struct GetRecipeQuery {
+    /// Scales ingredient quantities to this many servings.
+    #[serde(default)]
+    servings: Option<i32>,
+    /// Converts quantities given in the other system.
+    #[serde(default)]
+    units: Option<UnitSystem>,
+}
+
 async fn get_recipe(req: Request<AppContext>) -> tide::Result<Response> {
     let recipe_id = parse_param(&req, "id")?;
-    let res = match repository::get_recipe(&req.state().pool, recipe_id).await? {
-        Some(recipe) => Body::from_json(&recipe)?.into(),
-        None => StatusCode::NotFound.into(),
+    let query: GetRecipeQuery = req.query()?;
+    let recipe = match repository::get_recipe(&req.state().pool, recipe_id).await? {
+        Some(recipe) => recipe,
+        None => return Ok(StatusCode::NotFound.into()),
     };
-    Ok(res)
+
+    let recipe = match query.servings {
+        None => recipe,
+        Some(servings) if servings <= 0 => {
+            return Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                "Servings must be a positive number",
+            ))
+        }
+        Some(servings) => scaling::scale_recipe(recipe, servings).ok_or_else(|| {
+            tide::Error::from_str(
+                StatusCode::BadRequest,
+                format!("Recipe '{}' has no servings to scale from", recipe_id),
+            )
+        })?,
+    };
+
+    let recipe = match query.units {
+        None => recipe,
+        Some(system) => Recipe {
+            ingredients: recipe
+                .ingredients
+                .into_iter()
+                .map(|i| convert_ingredient(i, system))
+                .collect(),
+            ..recipe
+        },
+    };
+
+    Ok(Body::from_json(&recipe)?.into())
+}
+
+fn validate_servings(servings: Option<i32>) -> tide::Result<()> {
+    if matches!(servings, Some(servings) if servings <= 0) {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Servings must be a positive number",
+        ));
+    }
+    Ok(())
 }
 
 async fn create_recipe(mut req: Request<AppContext>) -> tide::Result<Response> {
     let new_recipe: NewRecipe = req.body_json().await?;
+    validate_servings(new_recipe.servings.flatten())?;
+    let created_recipe = repository::create_recipe(&req.state().pool, new_recipe).await?;
+
+    let body = Body::from_json(&created_recipe)?;
+    Ok(Response::builder(StatusCode::Created).body(body).build())
+}
+
+/// Creates a recipe from the raw HTML of a saved web page.
+async fn import_recipe(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let html = req.body_string().await?;
+    let new_recipe = match import::import_recipe(&html)? {
+        Some(new_recipe) => new_recipe,
+        None => {
+            return Err(tide::Error::from_str(
+                StatusCode::UnprocessableEntity,
+                "No schema.org recipe found in document",
+            ))
+        }
+    };
     let created_recipe = repository::create_recipe(&req.state().pool, new_recipe).await?;
 
     let body = Body::from_json(&created_recipe)?;
@@ -83,21 +242,128 @@ async fn create_recipe(mut req: Request<AppContext>) -> tide::Result<Response> {
 async fn update_recipe(mut req: Request<AppContext>) -> tide::Result<Response> {
     let recipe_id = parse_param(&req, "id")?;
     let recipe_data: NewRecipe = req.body_json().await?;
-    let updated_recipe = Recipe {
-        id: recipe_id,
-        name: recipe_data.name,
-        body: recipe_data.body,
-        quick: recipe_data.quick,
-    };
+    validate_servings(recipe_data.servings.flatten())?;
 
-    let updated_recipe = repository::update_recipe(&req.state().pool, updated_recipe).await?;
+    let updated_recipe =
+        repository::update_recipe(&req.state().pool, recipe_id, recipe_data).await?;
 
     let body = Body::from_json(&updated_recipe)?;
     Ok(Response::builder(StatusCode::Created).body(body).build())
 }
 
+#[derive(Deserialize)]
+struct DeleteRecipeQuery {
+    /// Deletes the recipe for good, removing it from past days too.
+    #[serde(default)]
+    purge: bool,
+}
+
+/// Archives the recipe unless a purge is asked for.
 async fn delete_recipe(req: Request<AppContext>) -> tide::Result<Response> {
     let recipe_id = parse_param(&req, "id")?;
-    repository::delete_recipe(&req.state().pool, recipe_id).await?;
+    let query: DeleteRecipeQuery = req.query()?;
+    if query.purge {
+        repository::delete_recipe(&req.state().pool, recipe_id).await?;
+    } else {
+        repository::archive_recipe(&req.state().pool, recipe_id).await?;
+    }
+    Ok(StatusCode::NoContent.into())
+}
+
+async fn restore_recipe(req: Request<AppContext>) -> tide::Result<Response> {
+    let recipe_id = parse_param(&req, "id")?;
+    let res = match repository::unarchive_recipe(&req.state().pool, recipe_id).await? {
+        Some(recipe) => Body::from_json(&recipe)?.into(),
+        None => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+#[derive(Serialize)]
+struct GetRevisionsResponse {
+    revisions: Vec<RecipeRevisionSummary>,
+}
+
+async fn get_revisions(req: Request<AppContext>) -> tide::Result<Body> {
+    let recipe_id = parse_param(&req, "id")?;
+    let revisions = repository::get_revisions(&req.state().pool, recipe_id).await?;
+    Body::from_json(&GetRevisionsResponse { revisions })
+}
+
+async fn get_revision(req: Request<AppContext>) -> tide::Result<Response> {
+    let recipe_id = parse_param(&req, "id")?;
+    let revision = parse_param(&req, "revision")?;
+    let res = match repository::get_revision(&req.state().pool, recipe_id, revision).await? {
+        Some(recipe_revision) => Body::from_json(&recipe_revision)?.into(),
+        None => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+async fn restore_revision(req: Request<AppContext>) -> tide::Result<Response> {
+    let recipe_id = parse_param(&req, "id")?;
+    let revision = parse_param(&req, "revision")?;
+    let res = match repository::restore_revision(&req.state().pool, recipe_id, revision).await? {
+        Some(recipe) => Body::from_json(&recipe)?.into(),
+        None => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: i32,
+    /// Compares with the current version when missing.
+    #[serde(default)]
+    to: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct DiffResponse {
+    from: i32,
+    to: Option<i32>,
+    lines: Vec<DiffLine>,
+}
+
+async fn diff_revisions(req: Request<AppContext>) -> tide::Result<Response> {
+    let recipe_id = parse_param(&req, "id")?;
+    let query: DiffQuery = req.query()?;
+    let pool = &req.state().pool;
+
+    let res = match (
+        repository::get_body_plain_text(pool, recipe_id, Some(query.from)).await?,
+        repository::get_body_plain_text(pool, recipe_id, query.to).await?,
+    ) {
+        (Some(old), Some(new)) => {
+            let lines = diff_lines(&old, &new).ok_or_else(|| {
+                tide::Error::from_str(
+                    StatusCode::UnprocessableEntity,
+                    "Revisions differ on too many lines to be compared",
+                )
+            })?;
+            Body::from_json(&DiffResponse {
+                from: query.from,
+                to: query.to,
+                lines,
+            })?
+            .into()
+        }
+        _ => StatusCode::NotFound.into(),
+    };
+    Ok(res)
+}
+
+#[derive(Serialize)]
+struct GetTagsResponse {
+    tags: Vec<Tag>,
+}
+
+async fn get_tags(req: Request<AppContext>) -> tide::Result<Body> {
+    let tags = repository::get_tags(&req.state().pool).await?;
+    Body::from_json(&GetTagsResponse { tags })
+}
+
+async fn delete_tag(req: Request<AppContext>) -> tide::Result<Response> {
+    repository::delete_tag(&req.state().pool, req.param("tag")?).await?;
     Ok(StatusCode::NoContent.into())
 }
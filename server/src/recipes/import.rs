@@ -0,0 +1,614 @@
+//! Turns a saved recipe web page into a `NewRecipe`, using the schema.org `Recipe`
+//! metadata most cooking websites embed, as JSON-LD or, failing that, as microdata.
+
+use std::{cell::RefCell, error::Error};
+
+use lol_html::{
+    element,
+    errors::RewritingError,
+    html_content::{Element, TextChunk},
+    rewrite_str, text, RewriteStrSettings,
+};
+use serde_json::Value;
+
+use crate::domain::{Ingredient, NewRecipe};
+use crate::html_filter::{decode_entities, to_plain_text};
+use crate::units;
+
+const JSON_LD_SELECTOR: &str = r#"script[type="application/ld+json"]"#;
+const MICRODATA_SELECTOR: &str = r#"[itemtype$="schema.org/Recipe"] [itemprop]"#;
+const MICRODATA_CONTENT_SELECTOR: &str = r#"[itemtype$="schema.org/Recipe"] [itemprop] *"#;
+
+const BLOCK_ELEMENTS: [&str; 8] = ["br", "div", "h3", "h4", "li", "ol", "p", "ul"];
+
+/// Recipes ready in this many minutes or less are imported as quick.
+const QUICK_MAX_MINUTES: u32 = 30;
+
+/// The parts of a schema.org `Recipe` we import, as raw text.
+#[derive(Debug, Default)]
+struct SchemaRecipe {
+    name: Option<String>,
+    description: Option<String>,
+    ingredients: Vec<String>,
+    instructions: Vec<InstructionSection>,
+    recipe_yield: Option<String>,
+    prep_time: Option<String>,
+    cook_time: Option<String>,
+    total_time: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct InstructionSection {
+    name: Option<String>,
+    steps: Vec<String>,
+}
+
+struct MicrodataProp {
+    name: String,
+    value: String,
+    /// Value taken from a `content` or `datetime` attribute rather than the element's text.
+    from_attribute: bool,
+}
+
+#[derive(Default)]
+struct ScannedDocument {
+    json_ld: Vec<String>,
+    microdata: Vec<MicrodataProp>,
+}
+
+/// Extracts the recipe described by an HTML document, `None` if it doesn't describe one.
+pub fn import_recipe(html: &str) -> anyhow::Result<Option<NewRecipe>> {
+    let document = scan_document(html)?;
+
+    let recipe = document
+        .json_ld
+        .iter()
+        .filter_map(|json| serde_json::from_str::<Value>(json).ok())
+        .find_map(|json| find_json_ld_recipe(&json).map(from_json_ld))
+        .or_else(|| from_microdata(&document.microdata));
+
+    match recipe {
+        Some(recipe) => recipe.into_new_recipe(),
+        None => Ok(None),
+    }
+}
+
+fn scan_document(html: &str) -> Result<ScannedDocument, RewritingError> {
+    let document = RefCell::new(ScannedDocument::default());
+
+    let start_json_ld = |_: &mut Element| -> Result<(), Box<dyn Error + Send + Sync>> {
+        document.borrow_mut().json_ld.push(String::new());
+        Ok(())
+    };
+    let read_json_ld = |t: &mut TextChunk| -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(json) = document.borrow_mut().json_ld.last_mut() {
+            json.push_str(t.as_str());
+        }
+        Ok(())
+    };
+    let start_prop = |el: &mut Element| -> Result<(), Box<dyn Error + Send + Sync>> {
+        let attribute_value = el
+            .get_attribute("content")
+            .or_else(|| el.get_attribute("datetime"));
+        document.borrow_mut().microdata.push(MicrodataProp {
+            name: el.get_attribute("itemprop").unwrap_or_default(),
+            from_attribute: attribute_value.is_some(),
+            value: attribute_value.unwrap_or_default(),
+        });
+        Ok(())
+    };
+    let break_prop_text = |el: &mut Element| -> Result<(), Box<dyn Error + Send + Sync>> {
+        if BLOCK_ELEMENTS.contains(&el.tag_name().as_str()) {
+            push_prop_text(&mut document.borrow_mut(), "\n");
+        }
+        Ok(())
+    };
+    let read_prop_text = |t: &mut TextChunk| -> Result<(), Box<dyn Error + Send + Sync>> {
+        push_prop_text(&mut document.borrow_mut(), t.as_str());
+        Ok(())
+    };
+
+    rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!(JSON_LD_SELECTOR, start_json_ld),
+                text!(JSON_LD_SELECTOR, read_json_ld),
+                element!(MICRODATA_CONTENT_SELECTOR, break_prop_text),
+                element!(MICRODATA_SELECTOR, start_prop),
+                text!(MICRODATA_SELECTOR, read_prop_text),
+            ],
+            ..Default::default()
+        },
+    )?;
+
+    Ok(document.into_inner())
+}
+
+/// Text goes to the last property opened, end tags are not tracked.
+fn push_prop_text(document: &mut ScannedDocument, text: &str) {
+    if let Some(prop) = document.microdata.last_mut() {
+        if !prop.from_attribute {
+            prop.value.push_str(text);
+        }
+    }
+}
+
+fn is_recipe_type(json: &Value) -> bool {
+    match json {
+        Value::String(t) => t == "Recipe" || t.ends_with("schema.org/Recipe"),
+        Value::Array(types) => types.iter().any(is_recipe_type),
+        _ => false,
+    }
+}
+
+/// Looks for the recipe in a JSON-LD document, which can be nested in a list or a graph.
+fn find_json_ld_recipe(json: &Value) -> Option<&Value> {
+    match json {
+        Value::Array(items) => items.iter().find_map(find_json_ld_recipe),
+        Value::Object(_) if is_recipe_type(&json["@type"]) => Some(json),
+        Value::Object(object) => ["@graph", "mainEntity"]
+            .iter()
+            .filter_map(|key| object.get(*key))
+            .find_map(find_json_ld_recipe),
+        _ => None,
+    }
+}
+
+fn json_text(json: &Value) -> Option<String> {
+    match json {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn json_texts(json: &Value) -> Vec<String> {
+    match json {
+        Value::Array(items) => items.iter().filter_map(json_text).collect(),
+        _ => json_text(json).into_iter().collect(),
+    }
+}
+
+fn json_ld_steps(json: &Value, sections: &mut Vec<InstructionSection>) {
+    match json {
+        Value::Array(items) => items.iter().for_each(|item| json_ld_steps(item, sections)),
+        Value::Object(step) if json["@type"] == "HowToSection" => {
+            sections.push(InstructionSection {
+                name: step.get("name").and_then(json_text),
+                steps: Vec::new(),
+            });
+            if let Some(items) = step.get("itemListElement") {
+                json_ld_steps(items, sections);
+            }
+        }
+        Value::Object(step) => {
+            if let Some(text) = step.get("text").or_else(|| step.get("name")) {
+                json_ld_steps(text, sections);
+            }
+        }
+        _ => {
+            if let Some(text) = json_text(json) {
+                if sections.is_empty() {
+                    sections.push(InstructionSection::default());
+                }
+                if let Some(section) = sections.last_mut() {
+                    section.steps.push(text);
+                }
+            }
+        }
+    }
+}
+
+fn from_json_ld(json: &Value) -> SchemaRecipe {
+    let mut instructions = Vec::new();
+    json_ld_steps(&json["recipeInstructions"], &mut instructions);
+
+    let ingredients = match json.get("recipeIngredient") {
+        Some(ingredients) => json_texts(ingredients),
+        None => json_texts(&json["ingredients"]),
+    };
+
+    SchemaRecipe {
+        name: json_text(&json["name"]),
+        description: json_text(&json["description"]),
+        ingredients,
+        instructions,
+        // Sites often give several yields, like ["4", "4 servings"], keep the most descriptive.
+        recipe_yield: json_texts(&json["recipeYield"])
+            .into_iter()
+            .max_by_key(|y| y.len()),
+        prep_time: json_text(&json["prepTime"]),
+        cook_time: json_text(&json["cookTime"]),
+        total_time: json_text(&json["totalTime"]),
+    }
+}
+
+fn from_microdata(props: &[MicrodataProp]) -> Option<SchemaRecipe> {
+    if props.is_empty() {
+        return None;
+    }
+
+    let values = |names: &[&str]| -> Vec<String> {
+        props
+            .iter()
+            .filter(|p| p.name.split_whitespace().any(|n| names.contains(&n)))
+            .map(|p| p.value.clone())
+            .filter(|v| !v.trim().is_empty())
+            .collect()
+    };
+    let first_value = |name: &str| values(&[name]).into_iter().next();
+
+    // Steps are either one element per step or a block with a step per line.
+    let steps = values(&["recipeInstructions", "text"])
+        .iter()
+        .flat_map(|block| block.lines().map(str::to_string).collect::<Vec<_>>())
+        .collect();
+
+    Some(SchemaRecipe {
+        name: first_value("name"),
+        description: first_value("description"),
+        ingredients: values(&["recipeIngredient", "ingredients"]),
+        instructions: vec![InstructionSection { name: None, steps }],
+        recipe_yield: first_value("recipeYield"),
+        prep_time: first_value("prepTime"),
+        cook_time: first_value("cookTime"),
+        total_time: first_value("totalTime"),
+    })
+}
+
+impl SchemaRecipe {
+    fn into_new_recipe(self) -> anyhow::Result<Option<NewRecipe>> {
+        let name = match self.name.as_deref().map(clean_text).transpose()? {
+            Some(name) if !name.is_empty() => name,
+            _ => return Ok(None),
+        };
+
+        let ingredients = clean_texts(&self.ingredients)?;
+        let prep_time = self.prep_time.as_deref().and_then(parse_duration);
+        let cook_time = self.cook_time.as_deref().and_then(parse_duration);
+        let total_time = self
+            .total_time
+            .as_deref()
+            .and_then(parse_duration)
+            .or_else(|| prep_time?.checked_add(cook_time?));
+
+        let mut body = Vec::new();
+
+        if let Some(description) = self.description.as_deref().map(clean_text).transpose()? {
+            if !description.is_empty() {
+                body.push(format!("<p>{}</p>", escape_html(&description)));
+            }
+        }
+
+        let mut details = Vec::new();
+        if let Some(recipe_yield) = self.recipe_yield.as_deref().map(clean_text).transpose()? {
+            details.push(format!("Yield: {}", recipe_yield));
+        }
+        for (label, minutes) in [
+            ("Prep time", prep_time),
+            ("Cook time", cook_time),
+            ("Total time", total_time),
+        ] {
+            if let Some(minutes) = minutes {
+                details.push(format!("{}: {}", label, format_duration(minutes)));
+            }
+        }
+        push_list(&mut body, "ul", &details);
+
+        if !ingredients.is_empty() {
+            body.push("<h2>Ingredients</h2>".to_string());
+            push_list(&mut body, "ul", &ingredients);
+        }
+
+        let mut sections = Vec::new();
+        for section in self.instructions {
+            let steps = clean_texts(&section.steps)?;
+            if !steps.is_empty() {
+                sections.push((section.name.as_deref().map(clean_text).transpose()?, steps));
+            }
+        }
+        if !sections.is_empty() {
+            body.push("<h2>Instructions</h2>".to_string());
+        }
+        for (name, steps) in sections {
+            if let Some(name) = name {
+                body.push(format!("<h3>{}</h3>", escape_html(&name)));
+            }
+            push_list(&mut body, "ol", &steps);
+        }
+
+        Ok(Some(NewRecipe {
+            name,
+            quick: matches!(total_time, Some(t) if t > 0 && t <= QUICK_MAX_MINUTES),
+            body: body.join("\n"),
+            servings: Some(self.recipe_yield.as_deref().and_then(parse_servings)),
+            ingredients: Some(ingredients.iter().map(|i| parse_ingredient(i)).collect()),
+            tags: None,
+        }))
+    }
+}
+
+fn push_list(body: &mut Vec<String>, list_tag: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    body.push(format!("<{}>", list_tag));
+    for item in items {
+        body.push(format!("  <li>{}</li>", escape_html(item)));
+    }
+    body.push(format!("</{}>", list_tag));
+}
+
+/// Strips markup and entities, and collapses whitespace, texts in pages are rarely clean.
+fn clean_text(s: &str) -> Result<String, RewritingError> {
+    let text = decode_entities(&to_plain_text(s)?);
+    Ok(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn clean_texts(texts: &[String]) -> Result<Vec<String>, RewritingError> {
+    let mut cleaned = Vec::new();
+    for text in texts {
+        let text = clean_text(text)?;
+        if !text.is_empty() {
+            cleaned.push(text);
+        }
+    }
+    Ok(cleaned)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Reads the number in yields like `4 servings` or `Serves 4`.
+fn parse_servings(recipe_yield: &str) -> Option<i32> {
+    recipe_yield
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|number| !number.is_empty())?
+        .parse()
+        .ok()
+        .filter(|servings| *servings > 0)
+}
+
+// Anything longer is more likely a typo than a recipe.
+const MAX_DURATION_MINUTES: f64 = 366.0 * 24.0 * 60.0;
+
+/// Parses ISO 8601 durations like `PT1H30M` into minutes, up to a year.
+fn parse_duration(s: &str) -> Option<u32> {
+    let s = s.trim().to_uppercase();
+    let mut in_time = false;
+    let mut number = String::new();
+    let mut minutes = 0.0;
+    let mut parsed = false;
+
+    for c in s.strip_prefix('P')?.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' | '.' => number.push(c),
+            _ => {
+                let value: f64 = number.parse().ok()?;
+                number.clear();
+                minutes += value
+                    * match (c, in_time) {
+                        ('W', false) => 7.0 * 24.0 * 60.0,
+                        ('D', false) => 24.0 * 60.0,
+                        ('H', true) => 60.0,
+                        ('M', true) => 1.0,
+                        ('S', true) => 1.0 / 60.0,
+                        _ => return None,
+                    };
+                parsed = true;
+            }
+        }
+    }
+
+    if parsed && number.is_empty() && minutes <= MAX_DURATION_MINUTES {
+        Some(minutes.round() as u32)
+    } else {
+        None
+    }
+}
+
+fn format_duration(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{} min", m),
+        (h, 0) => format!("{} h", h),
+        (h, m) => format!("{} h {} min", h, m),
+    }
+}
+
+/// Best effort split of a line like `1 1/2 cups flour, sifted` into its parts.
+fn parse_ingredient(line: &str) -> Ingredient {
+    let (main, note) = match line.split_once(',') {
+        Some((main, note)) => (main, Some(note.trim().to_string())),
+        None => (line, None),
+    };
+
+    let (quantity, unit, name) = match units::split_quantity(main) {
+        Some((quantity, name)) if !name.is_empty() => (
+            Some(quantity.amount),
+            quantity.unit.map(|u| u.symbol.to_string()),
+            name,
+        ),
+        _ => (None, None, main.trim()),
+    };
+
+    Ingredient {
+        name: name.strip_prefix("of ").unwrap_or(name).to_string(),
+        quantity,
+        unit,
+        note: note.filter(|n| !n.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str;
+
+    use super::*;
+
+    #[test]
+    fn import_recipe_from_json_ld() -> anyhow::Result<()> {
+        let html = str::from_utf8(include_bytes!("../../tests/data/recipe_json_ld.html"))?;
+
+        let recipe = import_recipe(html)?.expect("Should find a recipe");
+
+        assert_eq!(recipe.name, "Mom's Tomato & Basil Soup");
+        assert!(recipe.quick);
+        assert_eq!(recipe.servings, Some(Some(4)));
+        assert_eq!(
+            recipe.body,
+            r###"<p>A quick soup for cold evenings.</p>
+<ul>
+  <li>Yield: 4 servings</li>
+  <li>Prep time: 10 min</li>
+  <li>Cook time: 20 min</li>
+  <li>Total time: 30 min</li>
+</ul>
+<h2>Ingredients</h2>
+<ul>
+  <li>1 1/2 tbsp olive oil</li>
+  <li>2 onions, finely chopped</li>
+  <li>800g chopped tomatoes</li>
+  <li>A handful of basil leaves</li>
+</ul>
+<h2>Instructions</h2>
+<h3>Soup</h3>
+<ol>
+  <li>Soften the onions in the oil.</li>
+  <li>Add the tomatoes &amp; simmer for 20 minutes.</li>
+</ol>
+<h3>To serve</h3>
+<ol>
+  <li>Blend with the basil.</li>
+</ol>"###
+        );
+
+        let ingredients: Vec<_> = recipe
+            .ingredients
+            .iter()
+            .flatten()
+            .map(|i| {
+                (
+                    i.name.as_str(),
+                    i.quantity,
+                    i.unit.as_deref(),
+                    i.note.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ingredients,
+            [
+                ("olive oil", Some(1.5), Some("tbsp"), None),
+                ("onions", Some(2.0), None, Some("finely chopped")),
+                ("chopped tomatoes", Some(800.0), Some("g"), None),
+                ("A handful of basil leaves", None, None, None),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_recipe_from_microdata() -> anyhow::Result<()> {
+        let html = str::from_utf8(include_bytes!("../../tests/data/recipe_microdata.html"))?;
+
+        let recipe = import_recipe(html)?.expect("Should find a recipe");
+
+        assert_eq!(recipe.name, "Pancakes");
+        assert!(!recipe.quick);
+        assert_eq!(recipe.servings, Some(Some(8)));
+        assert_eq!(
+            recipe.body,
+            r###"<ul>
+  <li>Yield: 8 pancakes</li>
+  <li>Total time: 1 h 5 min</li>
+</ul>
+<h2>Ingredients</h2>
+<ul>
+  <li>200 g flour</li>
+  <li>2 eggs</li>
+  <li>300 ml milk</li>
+</ul>
+<h2>Instructions</h2>
+<ol>
+  <li>Whisk everything together.</li>
+  <li>Rest the batter for an hour.</li>
+  <li>Cook in a hot pan.</li>
+</ol>"###
+        );
+        let ingredients = recipe.ingredients.unwrap_or_default();
+        assert_eq!(ingredients[0].name, "flour");
+        assert_eq!(ingredients[0].quantity, Some(200.0));
+        assert_eq!(ingredients[0].unit.as_deref(), Some("g"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_recipe_without_recipe() -> anyhow::Result<()> {
+        let html = str::from_utf8(include_bytes!("../../tests/data/example.html"))?;
+
+        assert!(import_recipe(html)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_duration_handles_iso_8601_durations() {
+        assert_eq!(parse_duration("PT20M"), Some(20));
+        assert_eq!(parse_duration("PT1H30M"), Some(90));
+        assert_eq!(parse_duration("P0DT2H"), Some(120));
+        assert_eq!(parse_duration("PT90S"), Some(2));
+        assert_eq!(parse_duration("PT"), None);
+        assert_eq!(parse_duration("20 minutes"), None);
+        assert_eq!(parse_duration("P52W"), Some(52 * 7 * 24 * 60));
+        assert_eq!(parse_duration("P99999999W"), None);
+    }
+
+    #[test]
+    fn parse_ingredient_handles_quantities() {
+        let quantity = |line| parse_ingredient(line).quantity;
+
+        assert_eq!(quantity("2 eggs"), Some(2.0));
+        assert_eq!(quantity("1.5 l water"), Some(1.5));
+        assert_eq!(quantity("1/4 tsp salt"), Some(0.25));
+        assert_eq!(quantity("1½ cups sugar"), Some(1.5));
+        assert_eq!(quantity("½ lemon"), Some(0.5));
+        assert_eq!(quantity("2-3 carrots"), None);
+        assert_eq!(quantity("4x4 bread"), None);
+        assert_eq!(parse_ingredient("4x4 bread").name, "4x4 bread");
+    }
+
+    #[test]
+    fn parse_ingredient_splits_units_names_and_notes() {
+        let parts = |line| {
+            let i = parse_ingredient(line);
+            (i.name, i.quantity, i.unit, i.note)
+        };
+        let owned = |s: &str| Some(s.to_string());
+
+        assert_eq!(
+            parts("2 tsp. ground cumin"),
+            ("ground cumin".to_string(), Some(2.0), owned("tsp"), None)
+        );
+        assert_eq!(
+            parts("200g of flour, sifted"),
+            (
+                "flour".to_string(),
+                Some(200.0),
+                owned("g"),
+                owned("sifted")
+            )
+        );
+        assert_eq!(
+            parts("2 onions"),
+            ("onions".to_string(), Some(2.0), None, None)
+        );
+    }
+}
@@ -1,2 +1,6 @@
+pub mod diff;
 pub mod handlers;
+pub mod import;
+pub mod randomizer;
 pub mod repository;
+pub mod scaling;
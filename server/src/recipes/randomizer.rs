@@ -0,0 +1,266 @@
+//! Picks recipes at random, favouring the ones that weren't eaten much lately.
+
+use std::collections::HashMap;
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use time::Date;
+use uuid::Uuid;
+
+/// A recipe matching the filters of a slot, along with its history around the date.
+#[derive(Debug)]
+pub struct Candidate {
+    pub id: Uuid,
+    /// How many times it was planned, meals further from the date counting for less.
+    pub frequency: f64,
+    /// Planned too close to the date, only picked when nothing else is left.
+    pub recent: bool,
+    /// Foods about to expire it uses, when asked to favour those.
+    pub expiring_foods: i64,
+}
+
+/// Weighs the recipes on the dates they were planned on, meals count half as much
+/// every `half_life_days` away from `date` and are recent within `recent_days`.
+pub fn candidates_on(
+    date: Date,
+    recipe_ids: &[Uuid],
+    history: &HashMap<Uuid, Vec<Date>>,
+    recent_days: i32,
+    half_life_days: f64,
+) -> Vec<Candidate> {
+    recipe_ids
+        .iter()
+        .map(|&id| {
+            let days_away: Vec<i64> = history
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .map(|&planned| (planned - date).whole_days().abs())
+                .collect();
+            Candidate {
+                id,
+                frequency: days_away
+                    .iter()
+                    .map(|&days| 0.5f64.powf(days as f64 / half_life_days))
+                    .sum(),
+                recent: days_away.iter().any(|&days| days <= recent_days as i64),
+                expiring_foods: 0,
+            }
+        })
+        .collect()
+}
+
+// Never planned recipes count as planned half a time, so a recipe eaten
+// long ago can't end up more likely to be picked than a new one.
+const MIN_FREQUENCY: f64 = 0.5;
+
+// A recipe using two expiring foods is three times as likely to be picked.
+fn weight(candidate: &Candidate) -> f64 {
+    (1 + candidate.expiring_foods) as f64 / candidate.frequency.max(MIN_FREQUENCY)
+}
+
+/// Picks one of the candidates, the less often one was planned the more likely it is.
+pub fn choose<R: Rng + ?Sized>(rng: &mut R, candidates: &[&Candidate]) -> Option<Uuid> {
+    let index = WeightedIndex::new(candidates.iter().map(|c| weight(c))).ok()?;
+    Some(candidates[index.sample(rng)].id)
+}
+
+/// Picks a recipe that was neither planned recently nor already `picked`.
+/// Every candidate is allowed again once they have all been picked,
+/// then recent ones are allowed when that is still not enough.
+/// Picks that aren't candidates, like those of slots with other filters, stay excluded.
+pub fn pick_recipe<R: Rng + ?Sized>(
+    rng: &mut R,
+    candidates: &[Candidate],
+    picked: &mut Vec<Uuid>,
+) -> Option<Uuid> {
+    let eligible = |picked: &[Uuid]| -> Vec<&Candidate> {
+        candidates
+            .iter()
+            .filter(|c| !c.recent && !picked.contains(&c.id))
+            .collect()
+    };
+
+    let mut eligible_candidates = eligible(picked);
+    if eligible_candidates.is_empty() && !picked.is_empty() {
+        picked.retain(|id| !candidates.iter().any(|c| c.id == *id));
+        eligible_candidates = eligible(picked);
+    }
+    if eligible_candidates.is_empty() {
+        eligible_candidates = candidates.iter().collect();
+    }
+
+    let id = choose(rng, &eligible_candidates)?;
+    picked.push(id);
+    Some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use time::{Duration, Month};
+
+    use super::*;
+
+    fn candidate(n: u128, frequency: f64, recent: bool) -> Candidate {
+        Candidate {
+            id: Uuid::from_u128(n),
+            frequency,
+            recent,
+            expiring_foods: 0,
+        }
+    }
+
+    #[test]
+    fn candidates_on_weighs_history_around_the_date() {
+        let date = Date::from_calendar_date(2022, Month::January, 10).unwrap();
+        let (eaten, never) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let history = HashMap::from([(
+            eaten,
+            vec![date - Duration::days(2), date + Duration::days(4)],
+        )]);
+
+        let candidates = candidates_on(date, &[eaten, never], &history, 3, 2.0);
+
+        assert_eq!(candidates[0].frequency, 0.5 + 0.25);
+        assert!(candidates[0].recent);
+        assert_eq!(candidates[1].frequency, 0.0);
+        assert!(!candidates[1].recent);
+    }
+
+    #[test]
+    fn candidates_on_handles_old_history_and_short_half_lives() {
+        let date = Date::from_calendar_date(2022, Month::January, 10).unwrap();
+        let id = Uuid::from_u128(1);
+        let history = HashMap::from([(id, vec![date - Duration::days(1_400)])]);
+
+        let candidates = candidates_on(date, &[id], &history, 3, 0.1);
+
+        assert_eq!(candidates[0].frequency, 0.0);
+        assert!(!candidates[0].recent);
+    }
+
+    #[test]
+    fn choose_favours_rarely_planned_recipes() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let never = candidate(1, 0.0, false);
+        let often = candidate(2, 4.0, false);
+
+        let never_count = (0..10_000)
+            .filter(|_| choose(&mut rng, &[&never, &often]) == Some(never.id))
+            .count();
+
+        // Weighs 2 against 0.25, so picked 8 times out of 9.
+        assert!((8_600..9_200).contains(&never_count), "{}", never_count);
+    }
+
+    #[test]
+    fn choose_treats_faded_history_as_never_planned() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let never = candidate(1, 0.0, false);
+        let long_ago = candidate(2, 0.01, false);
+
+        let never_count = (0..10_000)
+            .filter(|_| choose(&mut rng, &[&never, &long_ago]) == Some(never.id))
+            .count();
+
+        assert!((4_700..5_300).contains(&never_count), "{}", never_count);
+    }
+
+    #[test]
+    fn choose_favours_recipes_using_expiring_foods() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let plain = candidate(1, 0.0, false);
+        let using_food = Candidate {
+            expiring_foods: 2,
+            ..candidate(2, 0.0, false)
+        };
+
+        let using_food_count = (0..10_000)
+            .filter(|_| choose(&mut rng, &[&plain, &using_food]) == Some(using_food.id))
+            .count();
+
+        assert!(
+            (7_200..7_800).contains(&using_food_count),
+            "{}",
+            using_food_count
+        );
+    }
+
+    #[test]
+    fn choose_needs_candidates() {
+        assert_eq!(choose(&mut StdRng::seed_from_u64(7), &[]), None);
+    }
+
+    #[test]
+    fn pick_recipe_skips_recent_and_picked_recipes() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let candidates = [
+            candidate(1, 0.0, true),
+            candidate(2, 0.0, false),
+            candidate(3, 0.0, false),
+        ];
+        let mut picked = vec![Uuid::from_u128(2)];
+
+        for _ in 0..20 {
+            picked.truncate(1);
+            assert_eq!(
+                pick_recipe(&mut rng, &candidates, &mut picked),
+                Some(Uuid::from_u128(3))
+            );
+        }
+    }
+
+    #[test]
+    fn pick_recipe_starts_over_then_allows_recent_recipes() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let candidates = [candidate(1, 0.0, true), candidate(2, 0.0, false)];
+        let mut picked = Vec::new();
+
+        assert_eq!(
+            pick_recipe(&mut rng, &candidates, &mut picked),
+            Some(Uuid::from_u128(2))
+        );
+        assert_eq!(
+            pick_recipe(&mut rng, &candidates, &mut picked),
+            Some(Uuid::from_u128(2))
+        );
+        assert_eq!(picked, [Uuid::from_u128(2)]);
+
+        let recent_only = [candidate(1, 0.0, true)];
+        assert_eq!(
+            pick_recipe(&mut rng, &recent_only, &mut picked),
+            Some(Uuid::from_u128(1))
+        );
+    }
+
+    #[test]
+    fn pick_recipe_only_starts_over_with_its_own_candidates() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let candidates = [candidate(1, 0.0, false)];
+        let mut picked = vec![Uuid::from_u128(1), Uuid::from_u128(2)];
+
+        assert_eq!(
+            pick_recipe(&mut rng, &candidates, &mut picked),
+            Some(Uuid::from_u128(1))
+        );
+        assert_eq!(picked, [Uuid::from_u128(2), Uuid::from_u128(1)]);
+    }
+
+    #[test]
+    fn pick_recipe_is_reproducible_with_a_seed() {
+        let candidates: Vec<Candidate> = (0..10).map(|n| candidate(n, 1.0, false)).collect();
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut picked = Vec::new();
+            for _ in 0..10 {
+                pick_recipe(&mut rng, &candidates, &mut picked);
+            }
+            picked
+        };
+
+        assert_eq!(picks(42), picks(42));
+        assert_ne!(picks(42), picks(43));
+    }
+}
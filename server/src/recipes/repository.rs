@@ -1,128 +1,763 @@
-use sqlx::{Executor, Postgres};
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, Executor, Postgres};
+use time::{Date, OffsetDateTime};
 
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewRecipe, Recipe},
+    domain::{Ingredient, NewRecipe, Recipe, RecipeRevision, RecipeRevisionSummary, Tag},
     html_filter,
 };
 
 pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> + Clone {}
 impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> + Clone {}
 
-pub async fn get_recipes<'a, E: PgExecutor<'a>>(
+#[derive(sqlx::FromRow)]
+pub struct RecipeDb {
+    pub id: Uuid,
+    pub name: String,
+    pub quick: bool,
+    #[sqlx(rename = "body_html")]
+    pub body: String,
+    pub servings: Option<i32>,
+    pub tags: Vec<String>,
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl RecipeDb {
+    pub fn into_recipe(self, ingredients: Vec<Ingredient>) -> Recipe {
+        Recipe {
+            id: self.id,
+            name: self.name,
+            quick: self.quick,
+            body: self.body,
+            servings: self.servings,
+            ingredients,
+            tags: self.tags,
+            archived_at: self.archived_at,
+        }
+    }
+}
+
+/// Restricts which recipes can be picked at random.
+#[derive(Clone)]
+pub struct RecipeFilter {
+    /// Only pick quick recipes when true.
+    pub quick: bool,
+    /// Recipes need every one of these tags.
+    pub include_tags: Vec<String>,
+    /// Recipes need none of these tags.
+    pub exclude_tags: Vec<String>,
+    /// Recipes planned this many days or less before or after the date
+    /// are only picked when nothing else is left.
+    pub recent_days: i32,
+    /// Past meals weigh half as much on how often a recipe is picked
+    /// every this many days away from the date.
+    pub half_life_days: f64,
+    /// Recipes using foods expiring within this many days are more likely to be picked.
+    pub expiring_within_days: Option<i32>,
+}
+
+/// Trims and lowercases tags, dropping empty and duplicate ones.
+pub fn normalize_tags<S: AsRef<str>>(tags: &[S]) -> Vec<String> {
+    tags.iter()
+        .map(|t| t.as_ref().trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+#[derive(sqlx::FromRow)]
+struct IngredientDb {
+    recipe_id: Uuid,
+    #[sqlx(flatten)]
+    ingredient: Ingredient,
+}
+
+// Ingredients are written as parallel arrays so they can be UNNESTed
+// in the same statement as the recipe they belong to.
+struct IngredientColumns {
+    names: Vec<String>,
+    quantities: Vec<Option<f64>>,
+    units: Vec<Option<String>>,
+    notes: Vec<Option<String>>,
+}
+
+impl From<&[Ingredient]> for IngredientColumns {
+    fn from(ingredients: &[Ingredient]) -> Self {
+        IngredientColumns {
+            names: ingredients.iter().map(|i| i.name.clone()).collect(),
+            quantities: ingredients.iter().map(|i| i.quantity).collect(),
+            units: ingredients.iter().map(|i| i.unit.clone()).collect(),
+            notes: ingredients.iter().map(|i| i.note.clone()).collect(),
+        }
+    }
+}
+
+pub async fn get_ingredients<'a, E: PgExecutor<'a>>(
+    exec: E,
+    recipe_ids: &[Uuid],
+) -> anyhow::Result<HashMap<Uuid, Vec<Ingredient>>> {
+    let rows: Vec<IngredientDb> = sqlx::query_as(
+        "SELECT recipe_id, name, quantity, unit, note
+         FROM recipe_ingredients
+         WHERE recipe_id = ANY($1)
+         ORDER BY recipe_id, position",
+    )
+    .bind(recipe_ids)
+    .fetch_all(exec)
+    .await?;
+
+    let mut ingredients: HashMap<Uuid, Vec<Ingredient>> = HashMap::new();
+    for row in rows {
+        ingredients
+            .entry(row.recipe_id)
+            .or_default()
+            .push(row.ingredient);
+    }
+
+    Ok(ingredients)
+}
+
+pub async fn with_ingredients<'a, E: PgExecutor<'a>>(
     exec: E,
-    query: Option<&str>,
-    limit: Option<i64>,
-    quick: Option<bool>,
+    recipes: Vec<RecipeDb>,
 ) -> anyhow::Result<Vec<Recipe>> {
-    let recipes = sqlx::query_as(
-        "
-         SELECT *
-         FROM recipes
-         WHERE ($1 IS NULL OR similarity(name, $1) > 0.1)
-           AND ($3 IS NULL OR quick = $3)
+    let ids: Vec<Uuid> = recipes.iter().map(|r| r.id).collect();
+    let mut ingredients = get_ingredients(exec, &ids).await?;
+
+    Ok(recipes
+        .into_iter()
+        .map(|r| {
+            let recipe_ingredients = ingredients.remove(&r.id).unwrap_or_default();
+            r.into_recipe(recipe_ingredients)
+        })
+        .collect())
+}
+
+/// Order of a recipe listing, ties are broken by name.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecipeSort {
+    /// Best matches of the search first.
+    Relevance,
+    Name,
+    /// Newest first.
+    CreatedAt,
+    /// Most recently cooked first, never cooked last.
+    LastCooked,
+    /// Most cooked first.
+    TimesCooked,
+}
+
+impl RecipeSort {
+    fn as_str(self) -> &'static str {
+        match self {
+            RecipeSort::Relevance => "relevance",
+            RecipeSort::Name => "name",
+            RecipeSort::CreatedAt => "createdAt",
+            RecipeSort::LastCooked => "lastCooked",
+            RecipeSort::TimesCooked => "timesCooked",
+        }
+    }
+}
+
+/// Position in a listing, holds the sort keys of the last recipe of a page.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeCursor {
+    pub sort: RecipeSort,
+    id: Uuid,
+    name: String,
+    rank: f32,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    last_cooked: Option<Date>,
+    times_cooked: i64,
+}
+
+impl RecipeCursor {
+    /// Opaque and URL safe representation handed out to clients.
+    pub fn encode(&self) -> anyhow::Result<String> {
+        Ok(base64::encode_config(
+            serde_json::to_vec(self)?,
+            base64::URL_SAFE_NO_PAD,
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> anyhow::Result<RecipeCursor> {
+        let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+pub struct RecipeListQuery {
+    pub search: Option<String>,
+    /// Searches the body too instead of fuzzy matching names.
+    pub full_text: bool,
+    pub quick: Option<bool>,
+    /// Recipes need every one of these tags.
+    pub tags: Vec<String>,
+    /// Only lists archived recipes when true.
+    pub archived: bool,
+    pub sort: RecipeSort,
+    /// Starts after this position, for the following pages.
+    pub after: Option<RecipeCursor>,
+    pub limit: Option<i64>,
+}
+
+pub struct RecipeListItem {
+    pub recipe: Recipe,
+    /// Extract of the body around the matches of a full-text search,
+    /// highlighted with `<mark>` tags.
+    pub snippet: Option<String>,
+}
+
+pub struct RecipePage {
+    pub recipes: Vec<RecipeListItem>,
+    /// Number of recipes matching the query over every page.
+    pub total: i64,
+    /// Set when there are more recipes after this page.
+    pub next: Option<RecipeCursor>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RecipeListRowDb {
+    #[sqlx(flatten)]
+    recipe: RecipeDb,
+    rank: f32,
+    created_at: OffsetDateTime,
+    last_cooked: Option<Date>,
+    times_cooked: i64,
+    snippet: Option<String>,
+}
+
+impl RecipeListRowDb {
+    fn cursor(&self, sort: RecipeSort) -> RecipeCursor {
+        RecipeCursor {
+            sort,
+            id: self.recipe.id,
+            name: self.recipe.name.clone(),
+            rank: self.rank,
+            created_at: self.created_at,
+            last_cooked: self.last_cooked,
+            times_cooked: self.times_cooked,
+        }
+    }
+}
+
+// Shared by the page and the total count, binds $1 to $5.
+const MATCHING_RECIPES: &str = "
+    WITH cooked AS (
+        SELECT recipe_id, max(date) AS last_cooked, count(*) AS times_cooked
+        FROM planned_meals
+        WHERE recipe_id IS NOT NULL AND date <= current_date
+        GROUP BY recipe_id
+    ), matching AS (
+        SELECT r.*,
+          coalesce((SELECT array_agg(t.tag ORDER BY t.tag) FROM recipe_tags t WHERE t.recipe_id = r.id), '{}') AS tags,
+          CASE WHEN $1::text IS NULL THEN 0
+               WHEN $2 THEN ts_rank(r.search_vector, websearch_to_tsquery('english', $1))
+               ELSE similarity(r.name, $1)
+          END::real AS rank,
+          c.last_cooked,
+          coalesce(c.times_cooked, 0) AS times_cooked
+        FROM recipes r
+        LEFT JOIN cooked c ON c.recipe_id = r.id
+        WHERE CASE WHEN $1 IS NULL THEN True
+                   WHEN $2 THEN r.search_vector @@ websearch_to_tsquery('english', $1)
+                   ELSE similarity(r.name, $1) > 0.1
+              END
+          AND ($3 IS NULL OR r.quick = $3)
+          AND (r.archived_at IS NOT NULL) = $5
+          AND cardinality($4::text[]) = (
+            SELECT count(*) FROM recipe_tags t
+            WHERE t.recipe_id = r.id AND t.tag = ANY($4)
+          )
+    )";
+
+/// A page of the recipes matching the query, along with how many match in total.
+pub async fn get_recipes<'a, E: PgExecutor<'a>>(
+    exec: E,
+    query: &RecipeListQuery,
+) -> anyhow::Result<RecipePage> {
+    let tags = normalize_tags(&query.tags);
+    let after = query.after.as_ref();
+
+    // Every sort is descending on its key then ascending on name and id,
+    // a page starts right after the cursor in that order.
+    let mut rows: Vec<RecipeListRowDb> = sqlx::query_as(&format!(
+        "{}
+         SELECT m.*,
+           CASE WHEN $1 IS NOT NULL AND $2 THEN
+             ts_headline(
+               'english', m.body_plain_text, websearch_to_tsquery('english', $1),
+               'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=5'
+             )
+           END AS snippet
+         FROM matching m
+         WHERE $7::uuid IS NULL OR CASE $6::text
+           WHEN 'relevance' THEN
+             m.rank < $9::real OR (m.rank = $9 AND (m.name, m.id) > ($8::text, $7))
+           WHEN 'createdAt' THEN
+             m.created_at < $10::timestamptz OR (m.created_at = $10 AND (m.name, m.id) > ($8, $7))
+           WHEN 'lastCooked' THEN
+             coalesce(m.last_cooked, '-infinity') < coalesce($11::date, '-infinity')
+             OR (coalesce(m.last_cooked, '-infinity') = coalesce($11, '-infinity')
+                 AND (m.name, m.id) > ($8, $7))
+           WHEN 'timesCooked' THEN
+             m.times_cooked < $12::bigint OR (m.times_cooked = $12 AND (m.name, m.id) > ($8, $7))
+           ELSE (m.name, m.id) > ($8, $7)
+         END
          ORDER BY
-           CASE WHEN $1 IS NOT NULL
-                THEN similarity(name, $1)
-           END DESC,
-           name ASC
-         LIMIT $2
+           CASE $6 WHEN 'relevance' THEN m.rank END DESC,
+           CASE $6 WHEN 'createdAt' THEN m.created_at END DESC,
+           CASE $6 WHEN 'lastCooked' THEN coalesce(m.last_cooked, '-infinity') END DESC,
+           CASE $6 WHEN 'timesCooked' THEN m.times_cooked END DESC,
+           m.name ASC,
+           m.id ASC
+         LIMIT $13
          ",
-    )
-    .bind(query)
-    .bind(limit)
-    .bind(quick)
-    .fetch_all(exec)
+        MATCHING_RECIPES
+    ))
+    .bind(query.search.as_deref())
+    .bind(query.full_text)
+    .bind(query.quick)
+    .bind(&tags)
+    .bind(query.archived)
+    .bind(query.sort.as_str())
+    .bind(after.map(|c| c.id))
+    .bind(after.map(|c| c.name.as_str()))
+    .bind(after.map(|c| c.rank))
+    .bind(after.map(|c| c.created_at))
+    .bind(after.and_then(|c| c.last_cooked))
+    .bind(after.map(|c| c.times_cooked))
+    // One more than asked for tells if there is a next page.
+    .bind(query.limit.map(|l| l + 1))
+    .fetch_all(exec.clone())
+    .await?;
+
+    let total = sqlx::query_scalar(&format!(
+        "{} SELECT count(*) FROM matching",
+        MATCHING_RECIPES
+    ))
+    .bind(query.search.as_deref())
+    .bind(query.full_text)
+    .bind(query.quick)
+    .bind(&tags)
+    .bind(query.archived)
+    .fetch_one(exec.clone())
     .await?;
 
-    Ok(recipes)
+    let next = match query.limit {
+        Some(limit) if rows.len() as i64 > limit => {
+            rows.truncate(limit as usize);
+            rows.last().map(|r| r.cursor(query.sort))
+        }
+        _ => None,
+    };
+
+    let (recipes, snippets): (Vec<RecipeDb>, Vec<Option<String>>) =
+        rows.into_iter().map(|r| (r.recipe, r.snippet)).unzip();
+    let recipes = with_ingredients(exec, recipes).await?;
+
+    Ok(RecipePage {
+        recipes: recipes
+            .into_iter()
+            .zip(snippets)
+            .map(|(recipe, snippet)| RecipeListItem { recipe, snippet })
+            .collect(),
+        total,
+        next,
+    })
 }
 
 pub async fn get_recipe<'a, E: PgExecutor<'a>>(
     exec: E,
     id: Uuid,
 ) -> anyhow::Result<Option<Recipe>> {
-    let recipe = sqlx::query_as(
-        "SELECT *
-         FROM recipes
+    let recipe: Option<RecipeDb> = sqlx::query_as(
+        "SELECT r.*,
+           coalesce((SELECT array_agg(t.tag ORDER BY t.tag) FROM recipe_tags t WHERE t.recipe_id = r.id), '{}') AS tags
+         FROM recipes r
          WHERE id = $1",
     )
     .bind(id)
-    .fetch_optional(exec)
+    .fetch_optional(exec.clone())
     .await?;
 
-    Ok(recipe)
+    match recipe {
+        Some(recipe) => Ok(with_ingredients(exec, vec![recipe]).await?.pop()),
+        None => Ok(None),
+    }
 }
 
-pub async fn get_random_recipe<'a, E: PgExecutor<'a>>(
+/// Ids of the recipes matching the filter, ordered.
+pub async fn get_random_recipe_ids<'a, E: Executor<'a, Database = Postgres>>(
     exec: E,
-    quick: bool,
-) -> anyhow::Result<Option<Recipe>> {
-    let recipe = sqlx::query_as(
-        "
-        WITH all_meals AS (
-            SELECT lunch_id AS id FROM days WHERE lunch_id IS NOT NULL
-            UNION ALL
-            SELECT dinner_id AS id FROM days WHERE dinner_id IS NOT NULL
-        ), frequencies AS (
-            SELECT id, count(id) AS frequency FROM all_meals GROUP BY id
-        )
-        SELECT r.*, coalesce(f.frequency, 0.5) AS frequency FROM frequencies f
-        FULL OUTER JOIN recipes r ON f.id = r.id
-        WHERE quick = true
-           OR quick = $1
-        ORDER BY log(random()) / coalesce(f.frequency, 0.5)
-        LIMIT 1
-        ",
+    filter: &RecipeFilter,
+) -> anyhow::Result<Vec<Uuid>> {
+    let ids = sqlx::query_scalar(
+        "SELECT r.id
+         FROM recipes r
+         WHERE (quick = true OR quick = $1)
+           AND r.archived_at IS NULL
+           AND cardinality($2::text[]) = (
+             SELECT count(*) FROM recipe_tags t
+             WHERE t.recipe_id = r.id AND t.tag = ANY($2)
+           )
+           AND NOT EXISTS (
+             SELECT 1 FROM recipe_tags t
+             WHERE t.recipe_id = r.id AND t.tag = ANY($3)
+           )
+         ORDER BY r.id",
     )
-    .bind(quick)
-    .fetch_optional(exec)
+    .bind(filter.quick)
+    .bind(normalize_tags(&filter.include_tags))
+    .bind(normalize_tags(&filter.exclude_tags))
+    .fetch_all(exec)
     .await?;
 
-    Ok(recipe)
+    Ok(ids)
 }
 
 pub async fn create_recipe<'a, E: PgExecutor<'a>>(
     exec: E,
     recipe: NewRecipe,
 ) -> anyhow::Result<Recipe> {
-    let created_recipe = sqlx::query_as(
-        "INSERT INTO recipes ( name, quick, body_html, body_plain_text )
-         VALUES ( $1, $2, $3, $4 )
-         RETURNING *",
+    let ingredients = recipe.ingredients.unwrap_or_default();
+    let columns = IngredientColumns::from(ingredients.as_slice());
+    let tags = normalize_tags(&recipe.tags.unwrap_or_default());
+    let body = html_filter::sanitize_html(&recipe.body)?;
+    let created_recipe: RecipeDb = sqlx::query_as(
+        "WITH created_recipe AS (
+             INSERT INTO recipes ( name, quick, body_html, body_plain_text, servings )
+             VALUES ( $1, $2, $3, $4, $5 )
+             RETURNING *
+         ), created_ingredients AS (
+             INSERT INTO recipe_ingredients ( recipe_id, position, name, quantity, unit, note )
+             SELECT r.id, i.position, i.name, i.quantity, i.unit, i.note
+             FROM created_recipe r,
+                  UNNEST($6::text[], $7::float8[], $8::text[], $9::text[])
+                    WITH ORDINALITY AS i(name, quantity, unit, note, position)
+         ), created_tags AS (
+             INSERT INTO recipe_tags ( recipe_id, tag )
+             SELECT r.id, t.tag
+             FROM created_recipe r, UNNEST($10::text[]) AS t(tag)
+         )
+         SELECT *, $10::text[] AS tags FROM created_recipe",
     )
     .bind(&recipe.name)
-    .bind(&recipe.quick)
-    .bind(&recipe.body)
-    .bind(&html_filter::to_plain_text(&recipe.body)?)
+    .bind(recipe.quick)
+    .bind(&body)
+    .bind(&html_filter::to_plain_text(&body)?)
+    .bind(recipe.servings.flatten())
+    .bind(&columns.names)
+    .bind(&columns.quantities)
+    .bind(&columns.units)
+    .bind(&columns.notes)
+    .bind(&tags)
     .fetch_one(exec)
     .await?;
 
-    Ok(created_recipe)
+    Ok(created_recipe.into_recipe(ingredients))
 }
 
-pub async fn update_recipe<'a, E: PgExecutor<'a>>(
-    exec: E,
-    recipe: Recipe,
-) -> anyhow::Result<Recipe> {
-    let created_recipe = sqlx::query_as(
-        "UPDATE recipes
-         SET name = $2, quick = $3, body_html = $4, body_plain_text = $5
-         WHERE id = $1
-         RETURNING *",
+/// Saves the current version as a new revision before updating the recipe.
+pub async fn update_recipe<'a, E>(exec: E, id: Uuid, recipe: NewRecipe) -> anyhow::Result<Recipe>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+{
+    let body = html_filter::sanitize_html(&recipe.body)?;
+    let mut tx = exec.begin().await?;
+
+    // Numbers revisions one update at a time.
+    sqlx::query("SELECT 1 FROM recipes WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let ingredients: Vec<Ingredient> = match recipe.ingredients {
+        Some(ingredients) => ingredients,
+        None => {
+            sqlx::query_as(
+                "SELECT name, quantity, unit, note
+             FROM recipe_ingredients
+             WHERE recipe_id = $1
+             ORDER BY position",
+            )
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .await?
+        }
+    };
+    let columns = IngredientColumns::from(ingredients.as_slice());
+    let servings: Option<i32> = match recipe.servings {
+        Some(servings) => servings,
+        None => {
+            sqlx::query_scalar("SELECT servings FROM recipes WHERE id = $1")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?
+        }
+    };
+    let tags = match recipe.tags {
+        Some(tags) => normalize_tags(&tags),
+        None => {
+            sqlx::query_scalar("SELECT tag FROM recipe_tags WHERE recipe_id = $1 ORDER BY tag")
+                .bind(id)
+                .fetch_all(&mut *tx)
+                .await?
+        }
+    };
+
+    let updated_recipe: RecipeDb = sqlx::query_as(
+        "WITH saved_revision AS (
+             INSERT INTO recipe_revisions
+               ( recipe_id, revision, name, quick, body_html, body_plain_text, servings )
+             SELECT id,
+                    coalesce((SELECT max(revision) FROM recipe_revisions WHERE recipe_id = $1), 0) + 1,
+                    name, coalesce(quick, False), body_html, body_plain_text, servings
+             FROM recipes
+             WHERE id = $1
+             RETURNING recipe_id, revision
+         ), saved_revision_ingredients AS (
+             INSERT INTO recipe_revision_ingredients
+               ( recipe_id, revision, position, name, quantity, unit, note )
+             SELECT s.recipe_id, s.revision, i.position, i.name, i.quantity, i.unit, i.note
+             FROM saved_revision s
+             JOIN recipe_ingredients i ON i.recipe_id = s.recipe_id
+         ), updated_recipe AS (
+             UPDATE recipes
+             SET name = $2, quick = $3, body_html = $4, body_plain_text = $5, servings = $6
+             WHERE id = $1
+             RETURNING *
+         ), deleted_ingredients AS (
+             DELETE FROM recipe_ingredients
+             WHERE recipe_id IN (SELECT id FROM updated_recipe)
+         ), created_ingredients AS (
+             INSERT INTO recipe_ingredients ( recipe_id, position, name, quantity, unit, note )
+             SELECT r.id, i.position, i.name, i.quantity, i.unit, i.note
+             FROM updated_recipe r,
+                  UNNEST($7::text[], $8::float8[], $9::text[], $10::text[])
+                    WITH ORDINALITY AS i(name, quantity, unit, note, position)
+         ), deleted_tags AS (
+             -- Only remove tags that are gone so re-inserted ones can't conflict.
+             DELETE FROM recipe_tags
+             WHERE recipe_id IN (SELECT id FROM updated_recipe)
+               AND NOT (tag = ANY($11))
+         ), created_tags AS (
+             INSERT INTO recipe_tags ( recipe_id, tag )
+             SELECT r.id, t.tag
+             FROM updated_recipe r, UNNEST($11::text[]) AS t(tag)
+             ON CONFLICT DO NOTHING
+         )
+         SELECT *, $11::text[] AS tags FROM updated_recipe",
     )
-    .bind(&recipe.id)
+    .bind(id)
     .bind(&recipe.name)
-    .bind(&recipe.quick)
-    .bind(&recipe.body)
-    .bind(&html_filter::to_plain_text(&recipe.body)?)
-    .fetch_one(exec)
+    .bind(recipe.quick)
+    .bind(&body)
+    .bind(&html_filter::to_plain_text(&body)?)
+    .bind(servings)
+    .bind(&columns.names)
+    .bind(&columns.quantities)
+    .bind(&columns.units)
+    .bind(&columns.notes)
+    .bind(&tags)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(updated_recipe.into_recipe(ingredients))
+}
+
+pub async fn get_revisions<'a, E: PgExecutor<'a>>(
+    exec: E,
+    recipe_id: Uuid,
+) -> anyhow::Result<Vec<RecipeRevisionSummary>> {
+    let revisions = sqlx::query_as(
+        "SELECT revision, created_at, name
+         FROM recipe_revisions
+         WHERE recipe_id = $1
+         ORDER BY revision DESC",
+    )
+    .bind(recipe_id)
+    .fetch_all(exec)
+    .await?;
+
+    Ok(revisions)
+}
+
+#[derive(sqlx::FromRow)]
+struct RecipeRevisionDb {
+    recipe_id: Uuid,
+    revision: i32,
+    created_at: OffsetDateTime,
+    name: String,
+    quick: bool,
+    #[sqlx(rename = "body_html")]
+    body: String,
+    servings: Option<i32>,
+}
+
+pub async fn get_revision<'a, E: PgExecutor<'a>>(
+    exec: E,
+    recipe_id: Uuid,
+    revision: i32,
+) -> anyhow::Result<Option<RecipeRevision>> {
+    let row: Option<RecipeRevisionDb> = sqlx::query_as(
+        "SELECT recipe_id, revision, created_at, name, quick, body_html, servings
+         FROM recipe_revisions
+         WHERE recipe_id = $1 AND revision = $2",
+    )
+    .bind(recipe_id)
+    .bind(revision)
+    .fetch_optional(exec.clone())
+    .await?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let ingredients = sqlx::query_as(
+        "SELECT name, quantity, unit, note
+         FROM recipe_revision_ingredients
+         WHERE recipe_id = $1 AND revision = $2
+         ORDER BY position",
+    )
+    .bind(recipe_id)
+    .bind(revision)
+    .fetch_all(exec)
     .await?;
 
-    Ok(created_recipe)
+    Ok(Some(RecipeRevision {
+        recipe_id: row.recipe_id,
+        revision: row.revision,
+        created_at: row.created_at,
+        name: row.name,
+        quick: row.quick,
+        body: row.body,
+        servings: row.servings,
+        ingredients,
+    }))
+}
+
+/// Plain text body of a revision of a recipe, or of its current version when `revision` is `None`.
+pub async fn get_body_plain_text<'a, E: PgExecutor<'a>>(
+    exec: E,
+    recipe_id: Uuid,
+    revision: Option<i32>,
+) -> anyhow::Result<Option<String>> {
+    let body = match revision {
+        Some(revision) => {
+            sqlx::query_scalar(
+                "SELECT body_plain_text FROM recipe_revisions
+                 WHERE recipe_id = $1 AND revision = $2",
+            )
+            .bind(recipe_id)
+            .bind(revision)
+            .fetch_optional(exec)
+            .await?
+        }
+        None => {
+            sqlx::query_scalar("SELECT body_plain_text FROM recipes WHERE id = $1")
+                .bind(recipe_id)
+                .fetch_optional(exec)
+                .await?
+        }
+    };
+
+    Ok(body)
+}
+
+/// Makes a revision the current version of the recipe, saving the current one as a new revision.
+pub async fn restore_revision<'a, E>(
+    exec: E,
+    recipe_id: Uuid,
+    revision: i32,
+) -> anyhow::Result<Option<Recipe>>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+{
+    let recipe_revision = match get_revision(exec.clone(), recipe_id, revision).await? {
+        Some(recipe_revision) => recipe_revision,
+        None => return Ok(None),
+    };
+
+    // Revisions don't keep tags, the current ones stay.
+    let restored = NewRecipe {
+        name: recipe_revision.name,
+        quick: recipe_revision.quick,
+        body: recipe_revision.body,
+        servings: Some(recipe_revision.servings),
+        ingredients: Some(recipe_revision.ingredients),
+        tags: None,
+    };
+
+    Ok(Some(update_recipe(exec, recipe_id, restored).await?))
+}
+
+/// Sanitizes bodies stored before they were sanitized on write, returns how many changed.
+pub async fn sanitize_recipe_bodies<'a, E>(exec: E) -> anyhow::Result<u64>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+{
+    let mut tx = exec.begin().await?;
+
+    let bodies: Vec<(Uuid, String)> = sqlx::query_as("SELECT id, body_html FROM recipes")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let mut sanitized_count = 0;
+    for (id, body) in bodies {
+        let sanitized_body = html_filter::sanitize_html(&body)?;
+        if sanitized_body == body {
+            continue;
+        }
+
+        sqlx::query("UPDATE recipes SET body_html = $2, body_plain_text = $3 WHERE id = $1")
+            .bind(id)
+            .bind(&sanitized_body)
+            .bind(&html_filter::to_plain_text(&sanitized_body)?)
+            .execute(&mut *tx)
+            .await?;
+        sanitized_count += 1;
+    }
+
+    tx.commit().await?;
+
+    Ok(sanitized_count)
 }
 
+/// Hides a recipe from listings and random picks, days it was planned on still show it.
+pub async fn archive_recipe<'a, E: PgExecutor<'a>>(exec: E, id: Uuid) -> anyhow::Result<()> {
+    sqlx::query("UPDATE recipes SET archived_at = now() WHERE id = $1 AND archived_at IS NULL")
+        .bind(id)
+        .execute(exec)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn unarchive_recipe<'a, E: PgExecutor<'a>>(
+    exec: E,
+    id: Uuid,
+) -> anyhow::Result<Option<Recipe>> {
+    sqlx::query("UPDATE recipes SET archived_at = NULL WHERE id = $1")
+        .bind(id)
+        .execute(exec.clone())
+        .await?;
+
+    get_recipe(exec, id).await
+}
+
+/// Permanently deletes a recipe along with every meal it was planned for.
 pub async fn delete_recipe<'a, E: PgExecutor<'a>>(exec: E, id: Uuid) -> anyhow::Result<()> {
     sqlx::query("DELETE FROM recipes WHERE id = $1")
         .bind(id)
@@ -131,3 +766,67 @@ pub async fn delete_recipe<'a, E: PgExecutor<'a>>(exec: E, id: Uuid) -> anyhow::
 
     Ok(())
 }
+
+pub async fn get_tags<'a, E: PgExecutor<'a>>(exec: E) -> anyhow::Result<Vec<Tag>> {
+    let tags = sqlx::query_as(
+        "SELECT tag AS name, count(*) AS count
+         FROM recipe_tags t
+         JOIN recipes r ON r.id = t.recipe_id
+         WHERE r.archived_at IS NULL
+         GROUP BY tag
+         ORDER BY tag ASC",
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(tags)
+}
+
+pub async fn delete_tag<'a, E: PgExecutor<'a>>(exec: E, tag: &str) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM recipe_tags WHERE tag = $1")
+        .bind(tag.trim().to_lowercase())
+        .execute(exec)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_tags_trims_lowercases_and_dedupes() {
+        let tags = [" Vegetarian", "summer", "vegetarian ", "", "  "];
+
+        let result = normalize_tags(&tags);
+
+        assert_eq!(result, vec!["summer", "vegetarian"]);
+    }
+
+    #[test]
+    fn recipe_cursor_round_trips_through_its_encoding() {
+        let cursor = RecipeCursor {
+            sort: RecipeSort::LastCooked,
+            id: Uuid::new_v4(),
+            name: "Crêpes".to_string(),
+            rank: 0.33,
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            last_cooked: Some(time::macros::date!(2022 - 01 - 31)),
+            times_cooked: 3,
+        };
+
+        let encoded = cursor.encode().expect("Should encode cursor");
+
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_".contains(c)));
+        assert_eq!(RecipeCursor::decode(&encoded).ok(), Some(cursor));
+    }
+
+    #[test]
+    fn recipe_cursor_rejects_garbage() {
+        assert!(RecipeCursor::decode("not a cursor").is_err());
+        assert!(RecipeCursor::decode("e30").is_err());
+    }
+}
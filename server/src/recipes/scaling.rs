@@ -0,0 +1,110 @@
+//! Scales the ingredient quantities of a recipe to a number of servings.
+
+use crate::domain::{Ingredient, Recipe};
+use crate::units::round_amount;
+
+fn scale_ingredient(ingredient: Ingredient, factor: f64) -> Ingredient {
+    let quantity = ingredient
+        .quantity
+        .map(|q| round_amount(q * factor, ingredient.unit.as_deref()));
+    Ingredient {
+        quantity,
+        ..ingredient
+    }
+}
+
+/// Scales every quantity from the servings of the recipe to `servings`,
+/// `None` if the recipe doesn't say how many it serves.
+pub fn scale_recipe(recipe: Recipe, servings: i32) -> Option<Recipe> {
+    let factor = f64::from(servings) / f64::from(recipe.servings?);
+    Some(Recipe {
+        servings: Some(servings),
+        ingredients: recipe
+            .ingredients
+            .into_iter()
+            .map(|i| scale_ingredient(i, factor))
+            .collect(),
+        ..recipe
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn ingredient(name: &str, quantity: Option<f64>, unit: Option<&str>) -> Ingredient {
+        Ingredient {
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(str::to_string),
+            note: None,
+        }
+    }
+
+    fn recipe(servings: Option<i32>, ingredients: Vec<Ingredient>) -> Recipe {
+        Recipe {
+            id: Uuid::new_v4(),
+            name: "Cake".to_string(),
+            quick: false,
+            body: String::new(),
+            servings,
+            ingredients,
+            tags: Vec::new(),
+            archived_at: None,
+        }
+    }
+
+    #[test]
+    fn scale_recipe_scales_and_rounds_quantities() {
+        let cake = recipe(
+            Some(4),
+            vec![
+                ingredient("eggs", Some(3.0), None),
+                ingredient("lemon", Some(0.5), None),
+                ingredient("flour", Some(250.0), Some("g")),
+                ingredient("vanilla", Some(1.0), Some("tsp")),
+                ingredient("milk", Some(0.4), Some("l")),
+                ingredient("salt", None, None),
+            ],
+        );
+
+        let scaled = scale_recipe(cake, 3).expect("Should scale recipe with servings");
+
+        assert_eq!(scaled.servings, Some(3));
+        let quantities: Vec<_> = scaled.ingredients.iter().map(|i| i.quantity).collect();
+        assert_eq!(
+            quantities,
+            [
+                Some(2.0),
+                Some(0.5),
+                Some(188.0),
+                Some(0.75),
+                Some(0.3),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn scale_recipe_keeps_small_quantities() {
+        let cake = recipe(
+            Some(8),
+            vec![
+                ingredient("egg", Some(1.0), None),
+                ingredient("baking soda", Some(0.25), Some("tsp")),
+            ],
+        );
+
+        let scaled = scale_recipe(cake, 1).expect("Should scale recipe with servings");
+
+        let quantities: Vec<_> = scaled.ingredients.iter().map(|i| i.quantity).collect();
+        assert_eq!(quantities, [Some(0.5), Some(0.125)]);
+    }
+
+    #[test]
+    fn scale_recipe_needs_servings() {
+        assert!(scale_recipe(recipe(None, Vec::new()), 2).is_none());
+    }
+}
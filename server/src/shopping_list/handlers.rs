@@ -0,0 +1,27 @@
+use serde::Deserialize;
+use tide::{Body, Request, Server};
+
+use crate::shopping_list::repository;
+use crate::tide_utils::parse_date_range_query;
+use crate::units::UnitSystem;
+use crate::AppContext;
+
+pub fn init(app: &mut Server<AppContext>) {
+    let mut shopping_list_api = app.at("/api/v0/shopping-list");
+    shopping_list_api.get(get_shopping_list);
+}
+
+#[derive(Deserialize)]
+struct ShoppingListQuery {
+    /// Converts quantities to this system, recipes' own units are kept otherwise.
+    #[serde(default)]
+    units: Option<UnitSystem>,
+}
+
+async fn get_shopping_list(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let query: ShoppingListQuery = req.query()?;
+    let shopping_list =
+        repository::get_shopping_list(&req.state().pool, range.from, range.to, query.units).await?;
+    Body::from_json(&shopping_list)
+}
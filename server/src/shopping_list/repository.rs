@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use sqlx::{Executor, Postgres};
+use time::Date;
+use uuid::Uuid;
+
+use crate::days::repository::get_planned_recipe_ids;
+use crate::domain::{Ingredient, ShoppingList, ShoppingListItem};
+use crate::foods::repository::get_foods_by_names;
+use crate::recipes::repository::get_ingredients;
+use crate::units::{
+    best_unit, density, find_unit, round_amount, Dimension, Quantity, Unit, UnitSystem,
+};
+
+pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> + Clone {}
+impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> + Clone {}
+
+pub async fn get_shopping_list<'a, E: PgExecutor<'a>>(
+    exec: E,
+    from: Date,
+    to: Date,
+    system: Option<UnitSystem>,
+) -> anyhow::Result<ShoppingList> {
+    let planned_ids = get_planned_recipe_ids(exec.clone(), from, to).await?;
+
+    let unique_ids: Vec<Uuid> = planned_ids
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let ingredients_by_recipe = get_ingredients(exec.clone(), &unique_ids).await?;
+
+    // A recipe planned twice needs its ingredients bought twice.
+    let ingredients = planned_ids
+        .iter()
+        .filter_map(|id| ingredients_by_recipe.get(id))
+        .flatten();
+    let mut items = aggregate_ingredients(ingredients, system);
+
+    let names: Vec<String> = items.iter().map(|i| normalize(&i.name)).collect();
+    let fridge_names: HashSet<String> = get_foods_by_names(exec, &names)
+        .await?
+        .iter()
+        .map(|f| normalize(&f.name))
+        .collect();
+    for item in items.iter_mut() {
+        item.in_fridge = fridge_names.contains(&normalize(&item.name));
+    }
+
+    Ok(ShoppingList { from, to, items })
+}
+
+fn normalize(s: &str) -> String {
+    s.trim().to_lowercase()
+}
+
+/// Running total of the quantities of an ingredient measured in one dimension.
+#[derive(Default)]
+struct MeasuredTotal {
+    /// In grams or millilitres.
+    amount: f64,
+    units: BTreeSet<&'static str>,
+}
+
+impl MeasuredTotal {
+    fn add(&mut self, amount: f64, unit: &'static Unit) {
+        self.amount += amount * unit.in_base;
+        self.units.insert(unit.symbol);
+    }
+
+    /// Keeps the unit of the recipes when they all agree, otherwise picks one.
+    fn to_quantity(&self, dimension: Dimension, system: Option<UnitSystem>) -> Option<Quantity> {
+        let shared_unit = match self.units.iter().collect::<Vec<_>>()[..] {
+            [unit] => find_unit(unit),
+            _ => None,
+        };
+        match shared_unit {
+            Some(unit) if system.is_none() || unit.system.is_none() || unit.system == system => {
+                Some(Quantity {
+                    amount: self.amount / unit.in_base,
+                    unit: Some(unit),
+                })
+            }
+            _ => best_unit(self.amount, dimension, system.unwrap_or(UnitSystem::Metric)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct IngredientTotal {
+    name: String,
+    mass: Option<MeasuredTotal>,
+    volume: Option<MeasuredTotal>,
+    /// Quantities that can't be converted, by unit, like eggs or cloves.
+    counts: BTreeMap<Option<String>, f64>,
+}
+
+impl IngredientTotal {
+    fn add(&mut self, ingredient: &Ingredient) {
+        let quantity = match ingredient.quantity {
+            Some(quantity) => quantity,
+            None => return,
+        };
+        match ingredient.unit.as_deref().map(|u| (u, find_unit(u))) {
+            Some((_, Some(unit))) if unit.dimension == Dimension::Mass => self
+                .mass
+                .get_or_insert_with(Default::default)
+                .add(quantity, unit),
+            Some((_, Some(unit))) if unit.dimension == Dimension::Volume => self
+                .volume
+                .get_or_insert_with(Default::default)
+                .add(quantity, unit),
+            Some((_, Some(unit))) => {
+                *self
+                    .counts
+                    .entry(Some(unit.symbol.to_string()))
+                    .or_default() += quantity
+            }
+            Some((unit, None)) => {
+                *self.counts.entry(Some(normalize(unit))).or_default() += quantity
+            }
+            None => *self.counts.entry(None).or_default() += quantity,
+        }
+    }
+
+    fn into_items(mut self, system: Option<UnitSystem>) -> Vec<ShoppingListItem> {
+        // Mass and volume of the same ingredient can be added up when its density is known.
+        if let (Some(mass), Some(volume)) = (&mut self.mass, &self.volume) {
+            if let Some(density) = density(&self.name) {
+                mass.amount += volume.amount * density;
+                mass.units.extend(&volume.units);
+                self.volume = None;
+            }
+        }
+
+        let item = |quantity: Option<f64>, unit: Option<String>| ShoppingListItem {
+            name: self.name.clone(),
+            quantity: quantity.map(|q| round_amount(q, unit.as_deref())),
+            unit,
+            in_fridge: false,
+        };
+
+        let mut items = Vec::new();
+        for (total, dimension) in [
+            (&self.mass, Dimension::Mass),
+            (&self.volume, Dimension::Volume),
+        ] {
+            if let Some(quantity) = total
+                .as_ref()
+                .and_then(|t| t.to_quantity(dimension, system))
+            {
+                items.push(item(
+                    Some(quantity.amount),
+                    quantity.unit.map(|u| u.symbol.to_string()),
+                ));
+            }
+        }
+        for (unit, amount) in &self.counts {
+            items.push(item(Some(*amount), unit.clone()));
+        }
+
+        // Ingredients never given a quantity still need to be on the list.
+        if items.is_empty() {
+            items.push(item(None, None));
+        }
+        items
+    }
+}
+
+/// Merges ingredients sharing the same name, adding up quantities in compatible units.
+/// Ingredients without a quantity are kept once so they still show up on the list.
+pub fn aggregate_ingredients<'a>(
+    ingredients: impl Iterator<Item = &'a Ingredient>,
+    system: Option<UnitSystem>,
+) -> Vec<ShoppingListItem> {
+    let mut totals: BTreeMap<String, IngredientTotal> = BTreeMap::new();
+
+    for ingredient in ingredients {
+        totals
+            .entry(normalize(&ingredient.name))
+            .or_insert_with(|| IngredientTotal {
+                name: ingredient.name.trim().to_string(),
+                ..Default::default()
+            })
+            .add(ingredient);
+    }
+
+    totals
+        .into_values()
+        .flat_map(|total| total.into_items(system))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ingredient(name: &str, quantity: Option<f64>, unit: Option<&str>) -> Ingredient {
+        Ingredient {
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(String::from),
+            note: None,
+        }
+    }
+
+    fn item(name: &str, quantity: Option<f64>, unit: Option<&str>) -> ShoppingListItem {
+        ShoppingListItem {
+            name: name.to_string(),
+            quantity,
+            unit: unit.map(String::from),
+            in_fridge: false,
+        }
+    }
+
+    #[test]
+    fn aggregate_ingredients_sums_same_name_and_unit() {
+        let ingredients = [
+            ingredient("Flour", Some(200.0), Some("g")),
+            ingredient("flour ", Some(50.0), Some("G")),
+            ingredient("Eggs", Some(2.0), None),
+            ingredient("eggs", Some(1.0), None),
+        ];
+
+        let result = aggregate_ingredients(ingredients.iter(), None);
+
+        assert_eq!(
+            result,
+            vec![
+                item("Eggs", Some(3.0), None),
+                item("Flour", Some(250.0), Some("g")),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_ingredients_converts_compatible_units() {
+        let ingredients = [
+            ingredient("Milk", Some(1.0), Some("cup")),
+            ingredient("Milk", Some(100.0), Some("ml")),
+            ingredient("Flour", Some(0.5), Some("kg")),
+            ingredient("Flour", Some(1.0), Some("cup")),
+            ingredient("Garlic", Some(2.0), Some("cloves")),
+            ingredient("Garlic", Some(1.0), Some("clove")),
+            ingredient("Gravel", Some(1.0), Some("cup")),
+            ingredient("Gravel", Some(1.0), Some("kg")),
+        ];
+
+        let result = aggregate_ingredients(ingredients.iter(), None);
+
+        assert_eq!(
+            result,
+            vec![
+                item("Flour", Some(625.0), Some("g")),
+                item("Garlic", Some(3.0), Some("clove")),
+                item("Gravel", Some(1.0), Some("kg")),
+                item("Gravel", Some(1.0), Some("cup")),
+                item("Milk", Some(337.0), Some("ml")),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_ingredients_follows_the_unit_system() {
+        let ingredients = [
+            ingredient("Butter", Some(100.0), Some("g")),
+            ingredient("Sugar", Some(2.0), Some("cups")),
+            ingredient("Oil", Some(2.0), Some("tbsp")),
+        ];
+
+        let result = aggregate_ingredients(ingredients.iter(), Some(UnitSystem::Imperial));
+        assert_eq!(
+            result,
+            vec![
+                item("Butter", Some(3.5), Some("oz")),
+                item("Oil", Some(2.0), Some("tbsp")),
+                item("Sugar", Some(2.0), Some("cup")),
+            ]
+        );
+
+        let result = aggregate_ingredients(ingredients.iter(), Some(UnitSystem::Metric));
+        assert_eq!(
+            result,
+            vec![
+                item("Butter", Some(100.0), Some("g")),
+                item("Oil", Some(2.0), Some("tbsp")),
+                item("Sugar", Some(473.0), Some("ml")),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_ingredients_keeps_items_without_quantity() {
+        let ingredients = [
+            ingredient("Salt", None, None),
+            ingredient("Salt", None, None),
+        ];
+
+        let result = aggregate_ingredients(ingredients.iter(), None);
+
+        assert_eq!(result, vec![item("Salt", None, None)]);
+    }
+}
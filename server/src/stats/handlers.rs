@@ -0,0 +1,23 @@
+use tide::{Body, Request, Server};
+
+use crate::stats::repository;
+use crate::tide_utils::parse_date_range_query;
+use crate::AppContext;
+
+pub fn init(app: &mut Server<AppContext>) {
+    let mut stats_api = app.at("/api/v0/stats");
+    stats_api.get(get_stats);
+    stats_api.at("/foods").get(get_food_stats);
+}
+
+async fn get_stats(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let stats = repository::get_stats(&req.state().pool, range.from, range.to).await?;
+    Body::from_json(&stats)
+}
+
+async fn get_food_stats(req: Request<AppContext>) -> tide::Result<Body> {
+    let range = parse_date_range_query(&req)?;
+    let stats = repository::get_food_stats(&req.state().pool, range.from, range.to).await?;
+    Body::from_json(&stats)
+}
@@ -0,0 +1,180 @@
+use sqlx::{Executor, Postgres};
+use time::Date;
+
+use crate::domain::{CheatMealStats, FoodStats, PeriodCount, QuickMealStats, RecipeStats, Stats};
+
+pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> + Clone {}
+impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> + Clone {}
+
+/// What was planned between `from` and `to`, both included.
+pub async fn get_stats<'a, E: PgExecutor<'a>>(
+    exec: E,
+    from: Date,
+    to: Date,
+) -> anyhow::Result<Stats> {
+    // Archived recipes are still counted, they were eaten all the same.
+    let recipes: Vec<RecipeStats> = sqlx::query_as(
+        "SELECT r.id, r.name, coalesce(r.quick, False) AS quick,
+           count(*) AS times_planned, max(pm.date) AS last_planned
+         FROM planned_meals pm
+         JOIN recipes r ON r.id = pm.recipe_id
+         WHERE pm.date BETWEEN $1 AND $2
+         GROUP BY r.id
+         ORDER BY times_planned DESC, r.name ASC",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(exec.clone())
+    .await?;
+
+    let never_planned = sqlx::query_as(
+        "SELECT r.id, r.name, coalesce(r.quick, False) AS quick
+         FROM recipes r
+         WHERE r.archived_at IS NULL
+           AND NOT EXISTS (
+             SELECT 1 FROM planned_meals pm
+             WHERE pm.recipe_id = r.id AND pm.date BETWEEN $1 AND $2
+           )
+         ORDER BY r.name ASC",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(exec.clone())
+    .await?;
+
+    let cheat_meals = CheatMealStats {
+        total: sqlx::query_scalar(
+            "SELECT count(*) FROM planned_meals
+             WHERE is_cheat AND date BETWEEN $1 AND $2",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_one(exec.clone())
+        .await?,
+        by_week: get_cheat_meals_by_period(exec.clone(), from, to, "week").await?,
+        by_month: get_cheat_meals_by_period(exec.clone(), from, to, "month").await?,
+    };
+
+    let quick: i64 = recipes
+        .iter()
+        .filter(|r| r.quick)
+        .map(|r| r.times_planned)
+        .sum();
+    let planned: i64 = recipes.iter().map(|r| r.times_planned).sum();
+    let quick_meals = QuickMealStats {
+        quick,
+        not_quick: planned - quick,
+        quick_ratio: match planned {
+            0 => None,
+            _ => Some(quick as f64 / planned as f64),
+        },
+    };
+
+    Ok(Stats {
+        from,
+        to,
+        recipes,
+        cheat_meals,
+        quick_meals,
+        never_planned,
+    })
+}
+
+/// Cheat meal counts for every week or month overlapping the range, including empty ones.
+async fn get_cheat_meals_by_period<'a, E: PgExecutor<'a>>(
+    exec: E,
+    from: Date,
+    to: Date,
+    period: &str,
+) -> anyhow::Result<Vec<PeriodCount>> {
+    let counts = sqlx::query_as(
+        "SELECT periods.start::date AS start, count(pm.date) AS count
+         FROM generate_series(
+           date_trunc($3, $1::date::timestamp), $2::date::timestamp, ('1 ' || $3)::interval
+         ) AS periods(start)
+         LEFT JOIN planned_meals pm
+           ON pm.is_cheat
+          AND date_trunc($3, pm.date::timestamp) = periods.start
+          AND pm.date BETWEEN $1 AND $2
+         GROUP BY periods.start
+         ORDER BY periods.start",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(period)
+    .fetch_all(exec)
+    .await?;
+
+    Ok(counts)
+}
+
+/// What became of the foods logged leaving between `from` and `to`, both included.
+pub async fn get_food_stats<'a, E: PgExecutor<'a>>(
+    exec: E,
+    from: Date,
+    to: Date,
+) -> anyhow::Result<FoodStats> {
+    let (consumed, thrown_away, given_away): (i64, i64, i64) = sqlx::query_as(
+        "SELECT
+           count(*) FILTER (WHERE reason = 'consumed'),
+           count(*) FILTER (WHERE reason = 'thrown_away'),
+           count(*) FILTER (WHERE reason = 'given_away')
+         FROM food_events
+         WHERE happened_on BETWEEN $1 AND $2",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_one(exec.clone())
+    .await?;
+
+    let waste_by_month = sqlx::query_as(
+        "SELECT months.start::date AS start, count(e.id) AS count
+         FROM generate_series(
+           date_trunc('month', $1::date::timestamp), $2::date::timestamp, '1 month'
+         ) AS months(start)
+         LEFT JOIN food_events e
+           ON e.reason = 'thrown_away'
+          AND date_trunc('month', e.happened_on::timestamp) = months.start
+          AND e.happened_on BETWEEN $1 AND $2
+         GROUP BY months.start
+         ORDER BY months.start",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(exec.clone())
+    .await?;
+
+    let most_wasted = sqlx::query_as(
+        "SELECT min(name) AS name, count(*) AS times_thrown_away
+         FROM food_events
+         WHERE reason = 'thrown_away' AND happened_on BETWEEN $1 AND $2
+         GROUP BY lower(name)
+         ORDER BY times_thrown_away DESC, name ASC
+         LIMIT 10",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(exec.clone())
+    .await?;
+
+    let average_days_before_expiry = sqlx::query_scalar(
+        "SELECT avg(expires_on - happened_on)::float8
+         FROM food_events
+         WHERE reason = 'consumed' AND happened_on BETWEEN $1 AND $2",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_one(exec)
+    .await?;
+
+    Ok(FoodStats {
+        from,
+        to,
+        consumed,
+        thrown_away,
+        given_away,
+        waste_by_month,
+        most_wasted,
+        average_days_before_expiry,
+    })
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+use tide::{Body, Request, Response, Server, StatusCode};
+
+use crate::domain::StorageLocation;
+use crate::storage_locations::repository::{self, LocationDeletion, LocationUpdate};
+use crate::AppContext;
+
+pub fn init(app: &mut Server<AppContext>) {
+    let mut locations_api = app.at("/api/v0/storage-locations");
+    locations_api.get(get_storage_locations);
+    locations_api.post(create_storage_location);
+    locations_api.at("/:name").put(update_storage_location);
+    locations_api.at("/:name").delete(delete_storage_location);
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GetStorageLocationsResponse {
+    storage_locations: Vec<StorageLocation>,
+}
+
+async fn get_storage_locations(req: Request<AppContext>) -> tide::Result<Body> {
+    let storage_locations = repository::get_storage_locations(&req.state().pool).await?;
+    Body::from_json(&GetStorageLocationsResponse { storage_locations })
+}
+
+fn ensure_valid_extension(location: &StorageLocation) -> tide::Result<()> {
+    match location.expiry_extension_days {
+        Some(days) if days <= 0 => Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Expiry extension must be a positive number of days",
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn create_storage_location(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let new_location: StorageLocation = req.body_json().await?;
+    ensure_valid_extension(&new_location)?;
+    let res = match repository::create_storage_location(&req.state().pool, new_location).await? {
+        Some(location) => Response::builder(StatusCode::Created)
+            .body(Body::from_json(&location)?)
+            .build(),
+        None => StatusCode::Conflict.into(),
+    };
+    Ok(res)
+}
+
+async fn update_storage_location(mut req: Request<AppContext>) -> tide::Result<Response> {
+    let location_data: StorageLocation = req.body_json().await?;
+    ensure_valid_extension(&location_data)?;
+    let name = req.param("name")?;
+    let res =
+        match repository::update_storage_location(&req.state().pool, name, location_data).await? {
+            Some(LocationUpdate::Updated(location)) => Body::from_json(&location)?.into(),
+            Some(LocationUpdate::NameTaken) => StatusCode::Conflict.into(),
+            None => StatusCode::NotFound.into(),
+        };
+    Ok(res)
+}
+
+/// Foods have to be moved out before their location can be deleted,
+/// and the last location can't be deleted.
+async fn delete_storage_location(req: Request<AppContext>) -> tide::Result<Response> {
+    let name = req.param("name")?;
+    match repository::delete_storage_location(&req.state().pool, name).await? {
+        Some(LocationDeletion::Deleted) => Ok(StatusCode::NoContent.into()),
+        Some(LocationDeletion::HasFoods) => Err(tide::Error::from_str(
+            StatusCode::Conflict,
+            format!("Storage location '{}' still has foods", name),
+        )),
+        Some(LocationDeletion::LastLocation) => Err(tide::Error::from_str(
+            StatusCode::Conflict,
+            format!("Storage location '{}' is the last one", name),
+        )),
+        None => Ok(StatusCode::NotFound.into()),
+    }
+}
@@ -0,0 +1,140 @@
+use sqlx::{Acquire, Executor, Postgres};
+
+use crate::domain::StorageLocation;
+
+pub trait PgExecutor<'a>: Executor<'a, Database = Postgres> {}
+impl<'a, T> PgExecutor<'a> for T where T: Executor<'a, Database = Postgres> {}
+
+pub async fn get_storage_locations<'a, E: PgExecutor<'a>>(
+    exec: E,
+) -> anyhow::Result<Vec<StorageLocation>> {
+    let locations = sqlx::query_as(
+        "SELECT *
+         FROM storage_locations
+         ORDER BY position ASC, name ASC",
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(locations)
+}
+
+pub async fn get_storage_location<'a, E: PgExecutor<'a>>(
+    exec: E,
+    name: &str,
+) -> anyhow::Result<Option<StorageLocation>> {
+    let location = sqlx::query_as(
+        "SELECT *
+         FROM storage_locations
+         WHERE name = $1",
+    )
+    .bind(name)
+    .fetch_optional(exec)
+    .await?;
+
+    Ok(location)
+}
+
+/// Returns `None` when a location with the same name already exists.
+pub async fn create_storage_location<'a, E: PgExecutor<'a>>(
+    exec: E,
+    location: StorageLocation,
+) -> anyhow::Result<Option<StorageLocation>> {
+    let created_location = sqlx::query_as(
+        "INSERT INTO storage_locations ( name, position, expiry_extension_days )
+         VALUES ( $1, $2, $3 )
+         ON CONFLICT (name) DO NOTHING
+         RETURNING *",
+    )
+    .bind(&location.name)
+    .bind(location.position)
+    .bind(location.expiry_extension_days)
+    .fetch_optional(exec)
+    .await?;
+
+    Ok(created_location)
+}
+
+pub enum LocationUpdate {
+    Updated(StorageLocation),
+    /// Another location already has the new name.
+    NameTaken,
+}
+
+/// Renaming a location carries its foods over.
+pub async fn update_storage_location<'a, E: PgExecutor<'a>>(
+    exec: E,
+    name: &str,
+    location: StorageLocation,
+) -> anyhow::Result<Option<LocationUpdate>> {
+    let updated_location = sqlx::query_as(
+        "UPDATE storage_locations
+         SET name = $2, position = $3, expiry_extension_days = $4
+         WHERE name = $1
+         RETURNING *",
+    )
+    .bind(name)
+    .bind(&location.name)
+    .bind(location.position)
+    .bind(location.expiry_extension_days)
+    .fetch_optional(exec)
+    .await;
+
+    match updated_location {
+        Ok(updated_location) => Ok(updated_location.map(LocationUpdate::Updated)),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+            Ok(Some(LocationUpdate::NameTaken))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+const UNIQUE_VIOLATION: &str = "23505";
+
+pub enum LocationDeletion {
+    Deleted,
+    /// Foods are still stored there.
+    HasFoods,
+    /// Foods always need somewhere to go.
+    LastLocation,
+}
+
+pub async fn delete_storage_location<'a, E>(
+    exec: E,
+    name: &str,
+) -> anyhow::Result<Option<LocationDeletion>>
+where
+    E: PgExecutor<'a> + Acquire<'a, Database = Postgres>,
+{
+    let mut tx = exec.begin().await?;
+
+    // Locked so two deletions can't remove the last two locations at once.
+    let names: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM storage_locations ORDER BY name FOR UPDATE")
+            .fetch_all(&mut *tx)
+            .await?;
+    if !names.iter().any(|n| n == name) {
+        return Ok(None);
+    }
+    if names.len() == 1 {
+        return Ok(Some(LocationDeletion::LastLocation));
+    }
+
+    let has_foods: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM foods WHERE location = $1)")
+            .bind(name)
+            .fetch_one(&mut *tx)
+            .await?;
+    if has_foods {
+        return Ok(Some(LocationDeletion::HasFoods));
+    }
+
+    sqlx::query("DELETE FROM storage_locations WHERE name = $1")
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(LocationDeletion::Deleted))
+}
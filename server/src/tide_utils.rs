@@ -1,6 +1,7 @@
 use std::{error::Error, fmt::Display};
 
 use lazy_static::lazy_static;
+use serde::Deserialize;
 use tide::{Request, StatusCode};
 use time::{
     error,
@@ -8,6 +9,8 @@ use time::{
     Date,
 };
 
+use crate::serde_iso_date;
+
 lazy_static! {
     static ref ISO_DATE_FORMAT: Vec<FormatItem<'static>> = format_description::parse("[year repr:full]-[month padding:zero]-[day]")
         .expect("Should be able to create format_description for '[year repr:full]-[month padding:zero]-[day]'");
@@ -39,12 +42,59 @@ where
     })
 }
 
+#[derive(Deserialize)]
+pub struct DateRangeQuery {
+    #[serde(with = "serde_iso_date")]
+    pub from: Date,
+    #[serde(with = "serde_iso_date")]
+    pub to: Date,
+}
+
+// Ranges span a year at most, so one request can't plan or report on centuries of days.
+const MAX_RANGE_DAYS: i64 = 366;
+
+pub fn parse_date_range_query<C>(req: &Request<C>) -> Result<DateRangeQuery, tide::Error> {
+    let query: DateRangeQuery = req.query()?;
+    if query.from > query.to {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "Query param 'from' should not be after 'to'",
+        ));
+    }
+    if (query.to - query.from).whole_days() >= MAX_RANGE_DAYS {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!("Date ranges should span {} days at most", MAX_RANGE_DAYS),
+        ));
+    }
+    Ok(query)
+}
+
+/// Splits a comma separated query param, ignoring blank entries.
+pub fn split_list(list: Option<&str>) -> Vec<String> {
+    list.map(|l| {
+        l.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use time::Month;
 
     use super::*;
 
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list(Some("a, b,,c ")), vec!["a", "b", "c"]);
+        assert_eq!(split_list(Some("")), Vec::<String>::new());
+        assert_eq!(split_list(None), Vec::<String>::new());
+    }
+
     // Mainly testing the format string is correct here.
     #[test]
     fn test_parse_iso_date() -> anyhow::Result<()> {
@@ -0,0 +1,413 @@
+//! Units of ingredient quantities: parsing free text like `1 1/2 tbsp`, converting
+//! between units, and between mass and volume using the density of the ingredient.
+
+use serde::Deserialize;
+
+use crate::domain::Ingredient;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dimension {
+    /// Measured in grams.
+    Mass,
+    /// Measured in millilitres.
+    Volume,
+    /// Cloves, cans, pinches... only comparable to the same unit.
+    Count,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rounding {
+    /// Whole items, halves under one.
+    Count,
+    /// Whole numbers from 10 up, tenths below.
+    Fine,
+    /// Quarters, like a kitchen's measuring spoons and cups.
+    Quarters,
+    Hundredths,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Unit {
+    pub symbol: &'static str,
+    aliases: &'static [&'static str],
+    pub dimension: Dimension,
+    /// How many grams, millilitres or items are in one of this unit.
+    pub in_base: f64,
+    /// `None` for units at home in both systems, like spoons.
+    pub system: Option<UnitSystem>,
+    rounding: Rounding,
+}
+
+const fn unit(
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    in_base: f64,
+    system: Option<UnitSystem>,
+    rounding: Rounding,
+) -> Unit {
+    Unit {
+        symbol,
+        aliases,
+        dimension,
+        in_base,
+        system,
+        rounding,
+    }
+}
+
+use Dimension::{Count, Mass, Volume};
+use UnitSystem::{Imperial, Metric};
+
+const METRIC: Option<UnitSystem> = Some(Metric);
+const IMPERIAL: Option<UnitSystem> = Some(Imperial);
+
+#[rustfmt::skip]
+static UNITS: [Unit; 24] = [
+    unit("mg", &["milligram", "milligrams"], Mass, 0.001, METRIC, Rounding::Fine),
+    unit("g", &["gr", "gram", "grams", "gramme", "grammes"], Mass, 1.0, METRIC, Rounding::Fine),
+    unit("kg", &["kilo", "kilos", "kilogram", "kilograms"], Mass, 1000.0, METRIC, Rounding::Hundredths),
+    unit("oz", &["ounce", "ounces"], Mass, 28.349523125, IMPERIAL, Rounding::Quarters),
+    unit("lb", &["lbs", "pound", "pounds"], Mass, 453.59237, IMPERIAL, Rounding::Hundredths),
+    unit("ml", &["millilitre", "millilitres", "milliliter", "milliliters"], Volume, 1.0, METRIC, Rounding::Fine),
+    unit("cl", &["centilitre", "centilitres", "centiliter", "centiliters"], Volume, 10.0, METRIC, Rounding::Fine),
+    unit("dl", &["decilitre", "decilitres", "deciliter", "deciliters"], Volume, 100.0, METRIC, Rounding::Fine),
+    unit("l", &["litre", "litres", "liter", "liters"], Volume, 1000.0, METRIC, Rounding::Hundredths),
+    unit("tsp", &["tsps", "teaspoon", "teaspoons"], Volume, 4.92892159375, None, Rounding::Quarters),
+    unit("tbsp", &["tbs", "tbsps", "tablespoon", "tablespoons"], Volume, 14.78676478125, None, Rounding::Quarters),
+    unit("fl oz", &["fluid ounce", "fluid ounces"], Volume, 29.5735295625, IMPERIAL, Rounding::Quarters),
+    unit("cup", &["cups"], Volume, 236.5882365, IMPERIAL, Rounding::Quarters),
+    unit("pint", &["pints", "pt"], Volume, 473.176473, IMPERIAL, Rounding::Quarters),
+    unit("quart", &["quarts", "qt"], Volume, 946.352946, IMPERIAL, Rounding::Quarters),
+    unit("gallon", &["gallons", "gal"], Volume, 3785.411784, IMPERIAL, Rounding::Hundredths),
+    unit("clove", &["cloves"], Count, 1.0, None, Rounding::Count),
+    unit("can", &["cans"], Count, 1.0, None, Rounding::Count),
+    unit("tin", &["tins"], Count, 1.0, None, Rounding::Count),
+    unit("pinch", &["pinches"], Count, 1.0, None, Rounding::Count),
+    unit("bunch", &["bunches"], Count, 1.0, None, Rounding::Count),
+    unit("slice", &["slices"], Count, 1.0, None, Rounding::Count),
+    unit("handful", &["handfuls"], Count, 1.0, None, Rounding::Count),
+    unit("piece", &["pieces"], Count, 1.0, None, Rounding::Count),
+];
+
+/// Grams per millilitre of common ingredients, matched on whole words of the name.
+const DENSITIES: [(&str, f64); 19] = [
+    ("water", 1.0),
+    ("milk", 1.03),
+    ("cream", 1.0),
+    ("yogurt", 1.03),
+    ("yoghurt", 1.03),
+    ("butter", 0.96),
+    ("oil", 0.92),
+    ("olive oil", 0.91),
+    ("honey", 1.42),
+    ("maple syrup", 1.32),
+    ("flour", 0.53),
+    ("sugar", 0.85),
+    ("brown sugar", 0.83),
+    ("icing sugar", 0.56),
+    ("powdered sugar", 0.56),
+    ("salt", 1.2),
+    ("rice", 0.85),
+    ("oats", 0.41),
+    ("cocoa", 0.42),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantity {
+    pub amount: f64,
+    pub unit: Option<&'static Unit>,
+}
+
+/// Finds a unit by symbol or name, ignoring case and trailing dots.
+pub fn find_unit(name: &str) -> Option<&'static Unit> {
+    let name = name.trim().trim_end_matches('.').to_lowercase();
+    UNITS
+        .iter()
+        .find(|u| u.symbol == name || u.aliases.contains(&name.as_str()))
+}
+
+/// Grams per millilitre of an ingredient, when it's one we know.
+pub fn density(ingredient: &str) -> Option<f64> {
+    let words: Vec<String> = ingredient
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let name = format!(" {} ", words.join(" "));
+
+    // The longest match wins so "brown sugar" isn't taken for "sugar".
+    DENSITIES
+        .iter()
+        .filter(|(key, _)| name.contains(&format!(" {} ", key)))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, density)| *density)
+}
+
+/// Picks the unit of `system` that reads best for an amount in grams or millilitres.
+pub fn best_unit(amount: f64, dimension: Dimension, system: UnitSystem) -> Option<Quantity> {
+    let symbol = match (dimension, system) {
+        (Count, _) => return None,
+        (Mass, Metric) if amount >= 1000.0 => "kg",
+        (Mass, Metric) => "g",
+        (Mass, Imperial) if amount >= 453.59237 => "lb",
+        (Mass, Imperial) => "oz",
+        (Volume, Metric) if amount >= 1000.0 => "l",
+        (Volume, Metric) => "ml",
+        (Volume, Imperial) if amount >= 236.5882365 / 4.0 => "cup",
+        (Volume, Imperial) if amount >= 14.78676478125 => "tbsp",
+        (Volume, Imperial) => "tsp",
+    };
+    let unit = find_unit(symbol)?;
+    Some(Quantity {
+        amount: amount / unit.in_base,
+        unit: Some(unit),
+    })
+}
+
+fn round_to(amount: f64, step: f64) -> f64 {
+    // Dividing by a whole number avoids float noise like 0.30000000000000004.
+    let steps_per_unit = (1.0 / step).round();
+    (amount * steps_per_unit).round() / steps_per_unit
+}
+
+/// Rounds an amount to something that can be measured in a kitchen.
+pub fn round_amount(amount: f64, unit: Option<&str>) -> f64 {
+    let rounding = match unit {
+        None => Rounding::Count,
+        Some(unit) => find_unit(unit).map_or(Rounding::Hundredths, |u| u.rounding),
+    };
+    let rounded = match rounding {
+        Rounding::Count if amount >= 1.0 => round_to(amount, 1.0),
+        Rounding::Count => round_to(amount, 0.5),
+        Rounding::Fine if amount >= 10.0 => round_to(amount, 1.0),
+        Rounding::Fine => round_to(amount, 0.1),
+        Rounding::Quarters => round_to(amount, 0.25),
+        Rounding::Hundredths => round_to(amount, 0.01),
+    };
+
+    // Don't round an ingredient away entirely.
+    if rounded == 0.0 && amount > 0.0 {
+        match rounding {
+            Rounding::Count => 0.5,
+            Rounding::Quarters => 0.125,
+            _ => round_to(amount, 0.001),
+        }
+    } else {
+        rounded
+    }
+}
+
+/// Converts the quantity of an ingredient given in the other system, spoons are left alone.
+pub fn convert_ingredient(ingredient: Ingredient, system: UnitSystem) -> Ingredient {
+    let unit = ingredient.unit.as_deref().and_then(find_unit);
+    match (ingredient.quantity, unit) {
+        (Some(amount), Some(unit)) if matches!(unit.system, Some(s) if s != system) => {
+            match best_unit(amount * unit.in_base, unit.dimension, system) {
+                Some(Quantity {
+                    amount,
+                    unit: Some(unit),
+                }) => Ingredient {
+                    quantity: Some(round_amount(amount, Some(unit.symbol))),
+                    unit: Some(unit.symbol.to_string()),
+                    ..ingredient
+                },
+                _ => ingredient,
+            }
+        }
+        _ => ingredient,
+    }
+}
+
+fn unicode_fraction(c: char) -> Option<f64> {
+    match c {
+        '¼' => Some(0.25),
+        '½' => Some(0.5),
+        '¾' => Some(0.75),
+        '⅓' => Some(1.0 / 3.0),
+        '⅔' => Some(2.0 / 3.0),
+        '⅛' => Some(0.125),
+        _ => None,
+    }
+}
+
+fn parse_decimal(s: &str) -> Option<f64> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn parse_fraction(s: &str) -> Option<f64> {
+    let mut chars = s.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return unicode_fraction(c);
+    }
+    let (numerator, denominator) = s.split_once('/')?;
+    let denominator = parse_decimal(denominator)?;
+    if denominator == 0.0 {
+        return None;
+    }
+    Some(parse_decimal(numerator)? / denominator)
+}
+
+/// Parses amounts like `2`, `1.5`, `1/2`, `½` or `1½`.
+pub fn parse_amount(s: &str) -> Option<f64> {
+    if let Some(amount) = parse_decimal(s).or_else(|| parse_fraction(s)) {
+        return Some(amount);
+    }
+    let last = s.chars().last()?;
+    let whole = parse_decimal(&s[..s.len() - last.len_utf8()])?;
+    Some(whole + unicode_fraction(last)?)
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(end) => (&s[..end], s[end..].trim_start()),
+        None => (s, ""),
+    }
+}
+
+/// Splits a unit off the start of `s`, units can be two words like `fl oz`.
+fn split_unit(s: &str) -> (Option<&'static Unit>, &str) {
+    let (first, rest) = split_word(s);
+    let (second, rest_after_second) = split_word(rest);
+    match find_unit(&format!("{} {}", first, second)) {
+        Some(unit) if !second.is_empty() => (Some(unit), rest_after_second),
+        _ => match find_unit(first) {
+            Some(unit) => (Some(unit), rest),
+            None => (None, s.trim_start()),
+        },
+    }
+}
+
+/// Splits a quantity like `1 1/2 tbsp` or `200g` off the start of a text,
+/// returning what follows it.
+pub fn split_quantity(text: &str) -> Option<(Quantity, &str)> {
+    let (first, rest) = split_word(text);
+
+    // Units can be stuck to the amount, like in `200g`.
+    let amount_end = first.find(char::is_alphabetic).unwrap_or(first.len());
+    let (amount, attached_unit) = first.split_at(amount_end);
+    let amount = parse_amount(amount)?;
+
+    if !attached_unit.is_empty() {
+        let unit = find_unit(attached_unit)?;
+        return Some((
+            Quantity {
+                amount,
+                unit: Some(unit),
+            },
+            rest,
+        ));
+    }
+
+    let (next, rest_after_next) = split_word(rest);
+    let (amount, rest) = match parse_fraction(next) {
+        Some(fraction) if amount.fract() == 0.0 => (amount + fraction, rest_after_next),
+        _ => (amount, rest),
+    };
+    let (unit, rest) = split_unit(rest);
+
+    Some((Quantity { amount, unit }, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ingredient(quantity: f64, unit: &str) -> Ingredient {
+        Ingredient {
+            name: "milk".to_string(),
+            quantity: Some(quantity),
+            unit: Some(unit.to_string()),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn split_quantity_handles_amounts_and_units() {
+        let parsed = |text| match split_quantity(text) {
+            Some((q, "")) => Some((q.amount, q.unit.map(|u| u.symbol))),
+            _ => None,
+        };
+
+        assert_eq!(parsed("1 1/2 tbsp"), Some((1.5, Some("tbsp"))));
+        assert_eq!(parsed("200g"), Some((200.0, Some("g"))));
+        assert_eq!(parsed("2 Cups"), Some((2.0, Some("cup"))));
+        assert_eq!(parsed("1½ fl oz"), Some((1.5, Some("fl oz"))));
+        assert_eq!(parsed("½"), Some((0.5, None)));
+        assert_eq!(parsed("3 cloves"), Some((3.0, Some("clove"))));
+        assert_eq!(parsed("2 onions"), None);
+        assert_eq!(parsed("2-3"), None);
+        assert_eq!(parsed("a pinch"), None);
+    }
+
+    #[test]
+    fn split_quantity_returns_the_rest_of_the_text() {
+        let (quantity, rest) = split_quantity("2 tsp. ground cumin").expect("Should parse");
+
+        assert_eq!(quantity.amount, 2.0);
+        assert_eq!(quantity.unit.map(|u| u.symbol), Some("tsp"));
+        assert_eq!(rest, "ground cumin");
+        assert!(split_quantity("4x4 bread").is_none());
+    }
+
+    #[test]
+    fn density_prefers_the_longest_match() {
+        assert_eq!(density("Light brown sugar"), Some(0.83));
+        assert_eq!(density("sugar"), Some(0.85));
+        assert_eq!(density("buttermilk"), None);
+    }
+
+    #[test]
+    fn best_unit_follows_the_system() {
+        let best = |amount, dimension, system| {
+            best_unit(amount, dimension, system).map(|q| (q.amount, q.unit.map(|u| u.symbol)))
+        };
+
+        assert_eq!(best(1500.0, Mass, Metric), Some((1.5, Some("kg"))));
+        assert_eq!(best(250.0, Volume, Metric), Some((250.0, Some("ml"))));
+        assert_eq!(best(907.18474, Mass, Imperial), Some((2.0, Some("lb"))));
+        assert_eq!(
+            best(236.5882365 / 2.0, Volume, Imperial),
+            Some((0.5, Some("cup")))
+        );
+        assert_eq!(best(1.0, Count, Metric), None);
+    }
+
+    #[test]
+    fn convert_ingredient_converts_units_of_the_other_system() {
+        let converted = convert_ingredient(ingredient(2.0, "cups"), Metric);
+        assert_eq!(converted.quantity, Some(473.0));
+        assert_eq!(converted.unit.as_deref(), Some("ml"));
+
+        let converted = convert_ingredient(ingredient(500.0, "g"), Imperial);
+        assert_eq!(converted.quantity, Some(1.1));
+        assert_eq!(converted.unit.as_deref(), Some("lb"));
+
+        let converted = convert_ingredient(ingredient(1.0, "tbsp"), Metric);
+        assert_eq!(converted.quantity, Some(1.0));
+        assert_eq!(converted.unit.as_deref(), Some("tbsp"));
+    }
+
+    #[test]
+    fn round_amount_depends_on_the_unit() {
+        assert_eq!(round_amount(2.4, None), 2.0);
+        assert_eq!(round_amount(0.3, None), 0.5);
+        assert_eq!(round_amount(187.5, Some("g")), 188.0);
+        assert_eq!(round_amount(2.46, Some("g")), 2.5);
+        assert_eq!(round_amount(0.8, Some("tsp")), 0.75);
+        assert_eq!(round_amount(0.05, Some("tsp")), 0.125);
+        assert_eq!(round_amount(1.234, Some("kg")), 1.23);
+        assert_eq!(round_amount(1.234, Some("handful of")), 1.23);
+    }
+}
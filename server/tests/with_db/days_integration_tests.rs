@@ -0,0 +1,613 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use slice_n_dice_server::init_app;
+use sqlx::PgPool;
+use tide::{
+    http::{Method, Request, Response},
+    StatusCode,
+};
+use time::{Duration, OffsetDateTime};
+
+use super::{api_url, create_recipe, emap};
+
+#[sqlx::test]
+async fn it_returns_every_day_in_a_range(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    // Create recipe
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({"name": "Food Stuff", "quick": true, "body": "<p>Paragraph</p>"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    // Plan some meals
+    let req = Request::new(Method::Put, api_url("/days/2022-01-02/lunch/randomize"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Put, api_url("/days/2022-01-03/dinner/cheat"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    // Retrieve range
+    let req = Request::new(Method::Get, api_url("/days?from=2022-01-01&to=2022-01-03"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let days = res_body["days"]
+        .as_array()
+        .ok_or(anyhow!("'.days' is not an array"))?;
+
+    assert_eq!(3, days.len());
+
+    assert_eq!("2022-01-01", days[0]["date"]);
+    assert_eq!(json!({"type": "unset"}), days[0]["lunch"]);
+    assert_eq!(json!({"type": "unset"}), days[0]["dinner"]);
+
+    assert_eq!("2022-01-02", days[1]["date"]);
+    assert_eq!("recipe", days[1]["lunch"]["type"]);
+    assert_eq!("Food Stuff", days[1]["lunch"]["name"]);
+    assert_eq!(json!({"type": "unset"}), days[1]["dinner"]);
+
+    assert_eq!("2022-01-03", days[2]["date"]);
+    assert_eq!(json!({"type": "unset"}), days[2]["lunch"]);
+    assert_eq!(json!({"type": "cheat"}), days[2]["dinner"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_rejects_a_range_with_invalid_dates(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req = Request::new(Method::Get, api_url("/days?from=2022-01-01&to=tomorrow"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let req = Request::new(Method::Get, api_url("/days?from=2022-01-01&to=2023-01-02"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/randomize?from=0001-01-01&to=9999-12-31"),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    // A whole leap year is fine
+    let req = Request::new(Method::Get, api_url("/days?from=2024-01-01&to=2024-12-31"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_randomizes_a_range_without_repeats(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for name in ["One", "Two", "Three", "Four"] {
+        create_recipe(&app, name, true).await?;
+    }
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/randomize?from=2022-01-01&to=2022-01-02"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let days = res_body["days"]
+        .as_array()
+        .ok_or(anyhow!("'.days' is not an array"))?;
+    assert_eq!(2, days.len());
+
+    let names: HashSet<&str> = days
+        .iter()
+        .flat_map(|d| [&d["lunch"]["name"], &d["dinner"]["name"]])
+        .filter_map(|n| n.as_str())
+        .collect();
+    assert_eq!(4, names.len());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_repeats_recipes_in_a_range_when_there_are_not_enough(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_recipe(&app, "Only One", true).await?;
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/randomize?from=2022-01-01&to=2022-01-02"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    for day in res_body["days"]
+        .as_array()
+        .ok_or(anyhow!("'.days' is not an array"))?
+    {
+        assert_eq!("Only One", day["lunch"]["name"]);
+        assert_eq!("Only One", day["dinner"]["name"]);
+    }
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_honours_the_quick_flag_per_slot(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_recipe(&app, "Quick", true).await?;
+    create_recipe(&app, "Slow", false).await?;
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/randomize?from=2022-01-01&to=2022-01-01&lunchQuick=true"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Quick", res_body["days"][0]["lunch"]["name"]);
+    assert_eq!("Slow", res_body["days"][0]["dinner"]["name"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_keeps_other_slots_from_repeating_when_one_runs_out(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_recipe(&app, "Quick", true).await?;
+    for name in ["Slow One", "Slow Two", "Slow Three"] {
+        create_recipe(&app, name, false).await?;
+    }
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/randomize?from=2022-01-01&to=2022-01-03&lunchQuick=true"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let days = res_body["days"]
+        .as_array()
+        .ok_or(anyhow!("'.days' is not an array"))?;
+    for day in days {
+        assert_eq!("Quick", day["lunch"]["name"]);
+    }
+    let dinners: HashSet<&str> = days
+        .iter()
+        .filter_map(|d| d["dinner"]["name"].as_str())
+        .collect();
+    assert_eq!(3, dinners.len());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_assigns_a_recipe_to_a_meal(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let recipe_id = create_recipe(&app, "Lasagne", false).await?;
+
+    let mut req = Request::new(Method::Put, api_url("/days/2022-01-04/dinner"));
+    req.set_body(json!({ "recipeId": recipe_id }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "unset"}), res_body["lunch"]);
+    assert_eq!("recipe", res_body["dinner"]["type"]);
+    assert_eq!(recipe_id, res_body["dinner"]["id"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_returns_not_found_when_assigning_an_unknown_recipe(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Put, api_url("/days/2022-01-04/lunch"));
+    req.set_body(json!({ "recipeId": "2bd1bbd4-3a0b-11ed-a261-0242ac120002" }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    let req = Request::new(Method::Get, api_url("/days/2022-01-04"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "unset"}), res_body["lunch"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_clears_meals_and_removes_empty_days(pool: PgPool) -> Result<()> {
+    let app = init_app(pool.clone());
+
+    create_recipe(&app, "Food Stuff", true).await?;
+
+    let req = Request::new(Method::Put, api_url("/days/2022-01-05/lunch/randomize"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Put, api_url("/days/2022-01-05/dinner/cheat"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    // Clear lunch, dinner stays a cheat meal
+    let req = Request::new(Method::Delete, api_url("/days/2022-01-05/lunch"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "unset"}), res_body["lunch"]);
+    assert_eq!(json!({"type": "cheat"}), res_body["dinner"]);
+
+    // Clear dinner, nothing is left so the row goes away
+    let req = Request::new(Method::Delete, api_url("/days/2022-01-05/dinner"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "unset"}), res_body["dinner"]);
+
+    let rows: i64 = sqlx::query_scalar("SELECT count(*) FROM planned_meals")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(0, rows);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_clears_a_whole_day(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_recipe(&app, "Food Stuff", true).await?;
+
+    let req = Request::new(Method::Put, api_url("/days/2022-01-06/randomize"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Delete, api_url("/days/2022-01-06"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Get, api_url("/days/2022-01-06"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "unset"}), res_body["lunch"]);
+    assert_eq!(json!({"type": "unset"}), res_body["dinner"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_randomizes_with_tag_filters(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for (name, tags) in [("Salad", json!(["vegetarian"])), ("Steak", json!(["meat"]))] {
+        let mut req = Request::new(Method::Post, api_url("/recipes"));
+        req.set_body(json!({"name": name, "quick": true, "body": "", "tags": tags}));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Created, res.status());
+    }
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/2022-01-01/randomize?includeTags=vegetarian"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Salad", res_body["lunch"]["name"]);
+    assert_eq!("Salad", res_body["dinner"]["name"]);
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/2022-01-02/lunch/randomize?excludeTags=vegetarian"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Steak", res_body["lunch"]["name"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_keeps_archived_recipes_in_past_days(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let archived_id = create_recipe(&app, "Archived", true).await?;
+    create_recipe(&app, "Kept", true).await?;
+
+    let mut req = Request::new(Method::Put, api_url("/days/2022-01-01/lunch"));
+    req.set_body(json!({ "recipeId": archived_id }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(
+        Method::Delete,
+        api_url(&format!("/recipes/{}", archived_id)),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Get, api_url("/days/2022-01-01"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Archived", res_body["lunch"]["name"]);
+    assert!(res_body["lunch"]["archivedAt"].is_string());
+
+    // Never picked at random or assigned again
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/randomize?from=2022-01-02&to=2022-01-03"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    for day in res_body["days"]
+        .as_array()
+        .ok_or(anyhow!("'.days' is not an array"))?
+    {
+        assert_eq!("Kept", day["lunch"]["name"]);
+        assert_eq!("Kept", day["dinner"]["name"]);
+    }
+
+    let mut req = Request::new(Method::Put, api_url("/days/2022-01-04/lunch"));
+    req.set_body(json!({ "recipeId": archived_id }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    // Purging removes it from history
+    let req = Request::new(
+        Method::Delete,
+        api_url(&format!("/recipes/{}?purge=true", archived_id)),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Get, api_url("/days/2022-01-01"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "unset"}), res_body["lunch"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_avoids_recently_planned_recipes(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let yesterday = create_recipe(&app, "Yesterday", true).await?;
+    let day_after = create_recipe(&app, "Day After", true).await?;
+    create_recipe(&app, "Fresh", true).await?;
+
+    for (date, recipe_id) in [("2022-01-02", &yesterday), ("2022-01-05", &day_after)] {
+        let mut req = Request::new(Method::Put, api_url(&format!("/days/{}/lunch", date)));
+        req.set_body(json!({ "recipeId": recipe_id }));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+    }
+
+    // The meal being replaced doesn't count, rerolling it keeps giving the same answer
+    for _ in 0..5 {
+        let req = Request::new(
+            Method::Put,
+            api_url("/days/2022-01-03/dinner/randomize?recentDays=2"),
+        );
+        let mut res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+        let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+        assert_eq!("Fresh", res_body["dinner"]["name"]);
+    }
+
+    // Every recipe is recent, one is still picked
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/2022-01-04/lunch/randomize?recentDays=7"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("recipe", res_body["lunch"]["type"]);
+
+    for half_life_days in ["0", "NaN", "inf"] {
+        let req = Request::new(
+            Method::Put,
+            api_url(&format!(
+                "/days/2022-01-05/lunch/randomize?halfLifeDays={}",
+                half_life_days
+            )),
+        );
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+    }
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_randomizes_with_old_history_and_a_short_half_life(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let recipe_id = create_recipe(&app, "Stew", false).await?;
+    let mut req = Request::new(Method::Put, api_url("/days/2018-03-01/dinner"));
+    req.set_body(json!({ "recipeId": recipe_id }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    for half_life_days in ["1", "0.1"] {
+        let req = Request::new(
+            Method::Put,
+            api_url(&format!(
+                "/days/2022-01-05/lunch/randomize?halfLifeDays={}",
+                half_life_days
+            )),
+        );
+        let mut res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+        let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+        assert_eq!("Stew", res_body["lunch"]["name"]);
+    }
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_randomizes_the_same_way_with_the_same_seed(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for name in ["One", "Two", "Three", "Four", "Five", "Six"] {
+        create_recipe(&app, name, true).await?;
+    }
+
+    let mut results = Vec::new();
+    for _ in 0..2 {
+        let req = Request::new(
+            Method::Put,
+            api_url("/days/randomize?from=2022-01-01&to=2022-01-07&seed=42"),
+        );
+        let mut res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+        let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+        results.push(res_body["days"].clone());
+    }
+    assert_eq!(results[0], results[1]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_keeps_locked_meals_when_randomizing(pool: PgPool) -> Result<()> {
+    let app = init_app(pool.clone());
+
+    let kept = create_recipe(&app, "Kept", true).await?;
+    for name in ["Two", "Three", "Four"] {
+        create_recipe(&app, name, true).await?;
+    }
+
+    let mut req = Request::new(Method::Put, api_url("/days/2022-01-03/lunch"));
+    req.set_body(json!({ "recipeId": kept }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Put, api_url("/days/2022-01-03/lunch/lock"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!(["lunch"]), res_body["lockedSlots"]);
+
+    for url in [
+        "/days/2022-01-03/randomize",
+        "/days/2022-01-03/lunch/randomize",
+        "/days/randomize?from=2022-01-02&to=2022-01-04",
+    ] {
+        let req = Request::new(Method::Put, api_url(url));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+
+        let req = Request::new(Method::Get, api_url("/days/2022-01-03"));
+        let mut res: Response = emap(app.respond(req).await)?;
+        let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+        assert_eq!("Kept", res_body["lunch"]["name"], "after {}", url);
+        assert_ne!("Kept", res_body["dinner"]["name"], "after {}", url);
+    }
+
+    let req = Request::new(Method::Delete, api_url("/days/2022-01-03/lunch/lock"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(Value::Null, res_body["lockedSlots"]);
+    assert_eq!("Kept", res_body["lunch"]["name"]);
+
+    // Locking an empty slot keeps it empty, unlocking it leaves nothing behind
+    let req = Request::new(Method::Put, api_url("/days/2022-01-10/dinner/lock"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Put, api_url("/days/2022-01-10/randomize"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("recipe", res_body["lunch"]["type"]);
+    assert_eq!(json!({"type": "unset"}), res_body["dinner"]);
+
+    let req = Request::new(Method::Delete, api_url("/days/2022-01-10/dinner/lock"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let rows: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM planned_meals WHERE date = '2022-01-10' AND slot = 'dinner'",
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(0, rows);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_favours_recipes_using_expiring_foods(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for name in ["One", "Two", "Three"] {
+        create_recipe(&app, name, true).await?;
+    }
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Omelette", "quick": true, "body": "",
+        "ingredients": [{"name": "eggs"}, {"name": "spinach"}, {"name": "mushrooms"}, {"name": "cheese"}]
+    }));
+    emap(app.respond::<_, Response>(req).await)?;
+
+    let tomorrow = OffsetDateTime::now_utc().date() + Duration::days(1);
+    for name in ["Eggs", "Spinach", "Mushrooms", "Cheese"] {
+        let mut req = Request::new(Method::Post, api_url("/foods"));
+        req.set_body(json!({"name": name, "bestBeforeDate": tomorrow.to_string()}));
+        emap(app.respond::<_, Response>(req).await)?;
+    }
+
+    let mut omelette_counts = Vec::new();
+    for params in ["", "&expiringWithinDays=3"] {
+        let mut count = 0;
+        for seed in 0..40 {
+            let req = Request::new(
+                Method::Put,
+                api_url(&format!(
+                    "/days/2022-01-01/lunch/randomize?seed={}{}",
+                    seed, params
+                )),
+            );
+            let mut res: Response = emap(app.respond(req).await)?;
+            assert_eq!(StatusCode::Ok, res.status());
+            let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+            if res_body["lunch"]["name"] == "Omelette" {
+                count += 1;
+            }
+        }
+        omelette_counts.push(count);
+    }
+    // Picked a quarter of the time, then five times out of eight.
+    assert!(
+        omelette_counts[1] > omelette_counts[0] + 8,
+        "{:?}",
+        omelette_counts
+    );
+
+    let req = Request::new(
+        Method::Put,
+        api_url("/days/2022-01-01/lunch/randomize?expiringWithinDays=-1"),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
@@ -0,0 +1,405 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use slice_n_dice_server::init_app;
+use sqlx::PgPool;
+use tide::{
+    http::{Method, Request, Response},
+    StatusCode,
+};
+use time::{Duration, OffsetDateTime};
+
+use super::{api_url, create_recipe_from, emap};
+
+#[sqlx::test]
+async fn it_updates_a_food_in_place(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(
+        json!({"name": "Chedar", "bestBeforeDate": "2022-01-10", "quantity": 200.0, "unit": "g"}),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let id = res_body["id"].as_i64().context("'.id' is not a number")?;
+
+    // Patch only touches what is given
+    let mut req = Request::new(Method::Patch, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"name": "Cheddar"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!({"id": id, "name": "Cheddar", "bestBeforeDate": "2022-01-10", "quantity": 200.0, "unit": "g",
+               "location": "fridge", "extendedBestBeforeDate": null}),
+        res_body
+    );
+
+    let mut req = Request::new(Method::Patch, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"bestBeforeDate": "2022-01-12", "unit": null}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("2022-01-12", res_body["bestBeforeDate"]);
+    assert_eq!(200.0, res_body["quantity"]);
+    assert_eq!(Value::Null, res_body["unit"]);
+
+    // Put replaces everything
+    let mut req = Request::new(Method::Put, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"name": "Cheddar", "bestBeforeDate": "2022-01-15"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!({"id": id, "name": "Cheddar", "bestBeforeDate": "2022-01-15", "quantity": null, "unit": null,
+               "location": "fridge", "extendedBestBeforeDate": null}),
+        res_body
+    );
+
+    let mut req = Request::new(Method::Patch, api_url("/foods/0"));
+    req.set_body(json!({"name": "Nothing"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    let mut req = Request::new(Method::Patch, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"quantity": 0}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_consumes_a_food_until_none_is_left(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(json!({"name": "Eggs", "bestBeforeDate": "2022-01-10", "quantity": 6}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let id = res_body["id"].as_i64().context("'.id' is not a number")?;
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/consume", id)));
+    req.set_body(json!({"quantity": 4}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(2.0, res_body["quantity"]);
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/consume", id)));
+    req.set_body(json!({"quantity": 3}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Get, api_url("/foods"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!([]), res_body["foods"]);
+
+    // Nothing to count down from
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(json!({"name": "Leftovers", "bestBeforeDate": "2022-01-10"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let id = res_body["id"].as_i64().context("'.id' is not a number")?;
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/consume", id)));
+    req.set_body(json!({"quantity": 1}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_moves_a_food_to_the_freezer(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(json!({"name": "Mince", "bestBeforeDate": "2022-01-10"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("fridge", res_body["location"]);
+    let id = res_body["id"].as_i64().context("'.id' is not a number")?;
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "freezer", "extendExpiry": true}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("freezer", res_body["location"]);
+    assert_eq!("2022-01-10", res_body["bestBeforeDate"]);
+    assert_eq!("2022-04-10", res_body["extendedBestBeforeDate"]);
+
+    // Thawed food goes back to its best before date
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "fridge"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("fridge", res_body["location"]);
+    assert_eq!(Value::Null, res_body["extendedBestBeforeDate"]);
+
+    // Fixing the best before date drops the extension made from the old one
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "freezer", "extendExpiry": true}));
+    emap(app.respond::<_, Response>(req).await)?;
+
+    let mut req = Request::new(Method::Patch, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"bestBeforeDate": "2022-01-12"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("freezer", res_body["location"]);
+    assert_eq!("2022-01-12", res_body["bestBeforeDate"]);
+    assert_eq!(Value::Null, res_body["extendedBestBeforeDate"]);
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "freezer", "extendExpiry": true}));
+    emap(app.respond::<_, Response>(req).await)?;
+
+    // Patching other fields keeps it
+    let mut req = Request::new(Method::Patch, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"name": "Beef mince", "bestBeforeDate": "2022-01-12"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("2022-04-12", res_body["extendedBestBeforeDate"]);
+
+    let mut req = Request::new(Method::Patch, api_url(&format!("/foods/{}", id)));
+    req.set_body(json!({"location": "pantry"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(Value::Null, res_body["extendedBestBeforeDate"]);
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "pantry", "extendExpiry": true}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "cellar"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let mut req = Request::new(Method::Post, api_url("/foods/0/move"));
+    req.set_body(json!({"location": "freezer"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_lists_foods_by_location(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for (name, date, location) in [
+        ("Peas", "2022-03-01", "freezer"),
+        ("Milk", "2022-01-12", "fridge"),
+        ("Rice", "2023-01-01", "pantry"),
+        ("Butter", "2022-01-10", "fridge"),
+    ] {
+        let mut req = Request::new(Method::Post, api_url("/foods"));
+        req.set_body(json!({"name": name, "bestBeforeDate": date, "location": location}));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Created, res.status());
+    }
+
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(json!({"name": "Wine", "bestBeforeDate": "2030-01-01", "location": "cellar"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let req = Request::new(Method::Get, api_url("/foods?location=fridge"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let names: Vec<&Value> = res_body["foods"]
+        .as_array()
+        .context("'.foods' is not an array")?
+        .iter()
+        .map(|f| &f["name"])
+        .collect();
+    assert_eq!(vec!["Butter", "Milk"], names);
+
+    let req = Request::new(Method::Get, api_url("/foods/by-location"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let locations: Vec<(&Value, Vec<&Value>)> = res_body["locations"]
+        .as_array()
+        .context("'.locations' is not an array")?
+        .iter()
+        .map(|l| {
+            let names = l["foods"].as_array().into_iter().flatten();
+            (&l["name"], names.map(|f| &f["name"]).collect())
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (&json!("fridge"), vec![&json!("Butter"), &json!("Milk")]),
+            (&json!("freezer"), vec![&json!("Peas")]),
+            (&json!("pantry"), vec![&json!("Rice")]),
+        ],
+        locations
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_manages_storage_locations(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req = Request::new(Method::Get, api_url("/storage-locations"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!([
+            {"name": "fridge", "position": 1, "expiryExtensionDays": null},
+            {"name": "freezer", "position": 2, "expiryExtensionDays": 90},
+            {"name": "pantry", "position": 3, "expiryExtensionDays": null}
+        ]),
+        res_body["storageLocations"]
+    );
+
+    let mut req = Request::new(Method::Post, api_url("/storage-locations"));
+    req.set_body(json!({"name": "chest", "position": 4, "expiryExtensionDays": 0}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let mut req = Request::new(Method::Post, api_url("/storage-locations"));
+    req.set_body(json!({"name": "chest", "position": 4, "expiryExtensionDays": 180}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(json!({"name": "Bread", "bestBeforeDate": "2022-01-10"}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let id = res_body["id"].as_i64().context("'.id' is not a number")?;
+
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/move", id)));
+    req.set_body(json!({"location": "chest", "extendExpiry": true}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("2022-07-09", res_body["extendedBestBeforeDate"]);
+
+    let req = Request::new(Method::Delete, api_url("/storage-locations/chest"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    // Renaming carries the foods over
+    let mut req = Request::new(Method::Put, api_url("/storage-locations/chest"));
+    req.set_body(json!({"name": "garage", "position": 4, "expiryExtensionDays": 180}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Get, api_url("/foods?location=garage"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Bread", res_body["foods"][0]["name"]);
+
+    let req = Request::new(
+        Method::Delete,
+        api_url(&format!("/foods/{}?reason=consumed", id)),
+    );
+    emap(app.respond::<_, Response>(req).await)?;
+
+    let req = Request::new(Method::Delete, api_url("/storage-locations/garage"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Delete, api_url("/storage-locations/garage"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    let mut req = Request::new(Method::Put, api_url("/storage-locations/pantry"));
+    req.set_body(json!({"name": "fridge", "position": 3, "expiryExtensionDays": null}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    for name in ["fridge", "freezer"] {
+        let req = Request::new(
+            Method::Delete,
+            api_url(&format!("/storage-locations/{}", name)),
+        );
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::NoContent, res.status());
+    }
+
+    let req = Request::new(Method::Delete, api_url("/storage-locations/pantry"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_suggests_recipes_using_foods_about_to_expire(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let today = OffsetDateTime::now_utc().date();
+    for (name, days) in [("Eggs", 1), ("Spinach", 2), ("Cream", 10), ("Milk", -1)] {
+        let mut req = Request::new(Method::Post, api_url("/foods"));
+        let date = today + Duration::days(days);
+        req.set_body(json!({"name": name, "bestBeforeDate": date.to_string()}));
+        emap(app.respond::<_, Response>(req).await)?;
+    }
+
+    for (name, ingredients, body) in [
+        ("Omelette", vec!["eggs", "baby spinach", "milk"], ""),
+        ("Quiche", vec!["egg", "cream"], ""),
+        ("Pasta", vec!["pasta"], "<p>Serve with spinach</p>"),
+        (
+            "Eggs florentine",
+            vec![],
+            "<p>Poach the eggs and wilt the spinach</p>",
+        ),
+    ] {
+        let ingredients: Vec<Value> = ingredients.iter().map(|i| json!({"name": i})).collect();
+        let recipe = json!({"name": name, "quick": true, "body": body, "ingredients": ingredients});
+        create_recipe_from(&app, recipe).await?;
+    }
+
+    let req = Request::new(Method::Get, api_url("/foods/suggestions"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        vec![
+            ("Eggs florentine", vec!["Eggs", "Spinach"]),
+            ("Omelette", vec!["Eggs", "Spinach"]),
+            ("Quiche", vec!["Eggs"]),
+        ],
+        suggested(&res_body)?
+    );
+    assert_eq!(
+        (today + Duration::days(1)).to_string(),
+        res_body["suggestions"][0]["foods"][0]["expiresOn"]
+    );
+
+    let req = Request::new(Method::Get, api_url("/foods/suggestions?days=10&limit=1"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        vec![("Eggs florentine", vec!["Eggs", "Spinach"])],
+        suggested(&res_body)?
+    );
+
+    let req = Request::new(Method::Get, api_url("/foods/suggestions?days=-1"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
+
+fn suggested(res_body: &Value) -> Result<Vec<(&str, Vec<&str>)>> {
+    res_body["suggestions"]
+        .as_array()
+        .context("'.suggestions' is not an array")?
+        .iter()
+        .map(|s| {
+            let foods = s["foods"].as_array().context("'.foods' is not an array")?;
+            Ok((
+                s["recipe"]["name"].as_str().context("no recipe name")?,
+                foods.iter().filter_map(|f| f["name"].as_str()).collect(),
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,212 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use slice_n_dice_server::init_app;
+use sqlx::PgPool;
+use tide::{
+    http::{Method, Request, Response},
+    StatusCode,
+};
+
+use super::{api_url, api_v1_url, create_slot, emap};
+
+#[sqlx::test]
+async fn it_lists_the_default_meal_slots(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req = Request::new(Method::Get, api_url("/meal-slots"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!([
+            {"name": "lunch", "position": 1, "randomizable": true},
+            {"name": "dinner", "position": 2, "randomizable": true}
+        ]),
+        res_body["mealSlots"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_rejects_duplicate_meal_slots(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/meal-slots"));
+    req.set_body(json!({"name": "lunch", "position": 3, "randomizable": true}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    create_slot(&app, "snack", 3, false).await?;
+    let mut req = Request::new(Method::Put, api_url("/meal-slots/snack"));
+    req.set_body(json!({"name": "lunch", "position": 3, "randomizable": false}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_plans_meals_in_custom_slots(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_slot(&app, "breakfast", 0, true).await?;
+    create_slot(&app, "snack", 3, false).await?;
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({"name": "Porridge", "quick": true, "body": "<p>Oats</p>"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    // Randomize the whole day, the snack slot is not randomizable
+    let req = Request::new(Method::Put, api_v1_url("/days/2022-01-01/randomize"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let meals = &res_body["meals"];
+    assert_eq!("Porridge", meals["breakfast"]["name"]);
+    assert_eq!("Porridge", meals["lunch"]["name"]);
+    assert_eq!("Porridge", meals["dinner"]["name"]);
+    assert_eq!(json!({"type": "unset"}), meals["snack"]);
+
+    // Snack can be cheated but not randomized
+    let req = Request::new(Method::Put, api_v1_url("/days/2022-01-01/snack/randomize"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let req = Request::new(Method::Put, api_v1_url("/days/2022-01-01/snack/cheat"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "cheat"}), res_body["meals"]["snack"]);
+
+    // The v0 shape only knows about lunch and dinner
+    let req = Request::new(Method::Get, api_url("/days/2022-01-01"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let keys: Vec<&String> = res_body
+        .as_object()
+        .ok_or(anyhow!("day is not an object"))?
+        .keys()
+        .collect();
+    assert_eq!(vec!["date", "dinner", "lunch"], keys);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_returns_not_found_for_unknown_slots(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req = Request::new(Method::Put, api_v1_url("/days/2022-01-01/brunch/cheat"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    let req = Request::new(Method::Delete, api_v1_url("/days/2022-01-01/brunch"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_keeps_planned_meals_when_renaming_a_slot(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_slot(&app, "tea", 3, true).await?;
+
+    let req = Request::new(Method::Put, api_v1_url("/days/2022-01-01/tea/cheat"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let mut req = Request::new(Method::Put, api_url("/meal-slots/tea"));
+    req.set_body(json!({"name": "supper", "position": 3, "randomizable": true}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Get, api_v1_url("/days/2022-01-01"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!({"type": "cheat"}), res_body["meals"]["supper"]);
+    assert_eq!(Value::Null, res_body["meals"]["tea"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_protects_the_built_in_slots(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Put, api_url("/meal-slots/dinner"));
+    req.set_body(json!({"name": "supper", "position": 2, "randomizable": true}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    let req = Request::new(Method::Delete, api_url("/meal-slots/lunch"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    // Other changes are fine
+    let mut req = Request::new(Method::Put, api_url("/meal-slots/dinner"));
+    req.set_body(json!({"name": "dinner", "position": 5, "randomizable": false}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Get, api_url("/days/2022-01-01"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_rejects_slot_names_used_by_routes(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for name in ["randomize", "", "a/b"] {
+        let mut req = Request::new(Method::Post, api_url("/meal-slots"));
+        req.set_body(json!({"name": name, "position": 3, "randomizable": true}));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+    }
+
+    create_slot(&app, "snack", 3, false).await?;
+    let mut req = Request::new(Method::Put, api_url("/meal-slots/snack"));
+    req.set_body(json!({"name": "randomize", "position": 3, "randomizable": false}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_only_deletes_slots_without_planned_meals(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    create_slot(&app, "snack", 3, false).await?;
+
+    let req = Request::new(Method::Put, api_v1_url("/days/2022-01-01/snack/cheat"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Delete, api_url("/meal-slots/snack"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Conflict, res.status());
+
+    let req = Request::new(Method::Delete, api_v1_url("/days/2022-01-01/snack"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let req = Request::new(Method::Delete, api_url("/meal-slots/snack"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Delete, api_url("/meal-slots/snack"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    Ok(())
+}
@@ -1 +1,87 @@
+mod days_integration_tests;
+mod foods_integration_tests;
+mod meal_slots_integration_tests;
+mod notifications_integration_tests;
 mod recipes_integration_tests;
+mod shopping_list_integration_tests;
+mod stats_integration_tests;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use slice_n_dice_server::AppContext;
+use tide::{
+    http::{Method, Request, Response, Url},
+    Server, StatusCode,
+};
+use uuid::Uuid;
+
+pub fn emap<T>(res: Result<T, tide::Error>) -> Result<T, anyhow::Error> {
+    res.map_err(tide::Error::into_inner)
+}
+
+pub fn api_url(sub_url: &str) -> Url {
+    let mut url_string = String::from("https://localhost/api/v0");
+    url_string.push_str(sub_url);
+    Url::parse(&url_string).expect("Could not create url")
+}
+
+pub fn api_v1_url(sub_url: &str) -> Url {
+    let mut url_string = String::from("https://localhost/api/v1");
+    url_string.push_str(sub_url);
+    Url::parse(&url_string).expect("Could not create url")
+}
+
+pub fn assert_json_is_uuid(v: &Value) -> Result<()> {
+    match v {
+        Value::String(s) => Uuid::parse_str(s)
+            .map(|_| ())
+            .context("Could not parse the json string into a UUID"),
+        _ => bail!("Json value should be a string to be a UUID"),
+    }
+}
+
+pub async fn create_recipe(app: &Server<AppContext>, name: &str, quick: bool) -> Result<String> {
+    create_recipe_from(
+        app,
+        json!({"name": name, "quick": quick, "body": "<p>Paragraph</p>"}),
+    )
+    .await
+}
+
+pub async fn create_recipe_from(app: &Server<AppContext>, recipe: Value) -> Result<String> {
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(recipe);
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    res_body["id"]
+        .as_str()
+        .map(String::from)
+        .ok_or(anyhow!("'.id' is not a string"))
+}
+
+pub async fn create_food(app: &Server<AppContext>, food: Value) -> Result<i64> {
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(food);
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    res_body["id"]
+        .as_i64()
+        .ok_or(anyhow!("'.id' is not a number"))
+}
+
+pub async fn create_slot(
+    app: &Server<AppContext>,
+    name: &str,
+    position: i32,
+    randomizable: bool,
+) -> Result<()> {
+    let mut req = Request::new(Method::Post, api_url("/meal-slots"));
+    req.set_body(json!({"name": name, "position": position, "randomizable": randomizable}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::net::TcpListener as StdTcpListener;
+
+use anyhow::Result;
+use async_std::channel::{self, Receiver, Sender};
+use async_std::io::{prelude::BufReadExt, BufReader, WriteExt};
+use async_std::net::TcpListener;
+use serde_json::{json, Value};
+use slice_n_dice_server::init_app;
+use slice_n_dice_server::notifications::{
+    notify_expiring_foods, Notifier, SmtpNotifier, WebhookNotifier,
+};
+use sqlx::PgPool;
+use tide::{
+    http::{Method, Request, Response},
+    StatusCode,
+};
+use time::{Date, Duration, OffsetDateTime};
+
+use super::{api_url, emap};
+
+#[sqlx::test]
+async fn it_posts_expiring_foods_to_a_webhook(pool: PgPool) -> Result<()> {
+    let today = create_foods(&pool).await?;
+
+    let (url, receiver) = webhook_stand_in()?;
+
+    let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(WebhookNotifier::new(&url)?)];
+    assert!(notify_expiring_foods(&pool, 3, &notifiers).await?);
+
+    let digest = receiver.recv().await?;
+    assert_eq!(json!(today.to_string()), digest["date"]);
+    assert_eq!(json!(3), digest["withinDays"]);
+    let names: Vec<&Value> = digest["foods"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|f| &f["name"])
+        .collect();
+    assert_eq!(vec!["Milk", "Eggs"], names);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_emails_expiring_foods(pool: PgPool) -> Result<()> {
+    let today = create_foods(&pool).await?;
+
+    let (sender, receiver) = channel::unbounded();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("smtp://{}", listener.local_addr()?);
+    async_std::task::spawn(smtp_stand_in(listener, sender));
+
+    let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(SmtpNotifier::new(
+        &url,
+        "Slice n Dice <slice@localhost>",
+        "me@localhost",
+    )?)];
+    assert!(notify_expiring_foods(&pool, 3, &notifiers).await?);
+
+    let email = receiver.recv().await?;
+    assert!(
+        email.contains("Subject: 2 foods about to expire"),
+        "{}",
+        email
+    );
+    assert!(email.contains("To: me@localhost"), "{}", email);
+    let yesterday = today - Duration::days(1);
+    let in_two_days = today + Duration::days(2);
+    assert!(
+        email.contains(&format!("- Milk (fridge): expired on {}", yesterday)),
+        "{}",
+        email
+    );
+    assert!(
+        email.contains(&format!("- Eggs (fridge): expires on {}", in_two_days)),
+        "{}",
+        email
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_tries_every_notifier_when_one_fails(pool: PgPool) -> Result<()> {
+    create_foods(&pool).await?;
+    let (url, receiver) = webhook_stand_in()?;
+
+    let notifiers: Vec<Box<dyn Notifier>> = vec![
+        Box::new(WebhookNotifier::new("http://127.0.0.1:9/hook")?),
+        Box::new(WebhookNotifier::new(&url)?),
+    ];
+    assert!(notify_expiring_foods(&pool, 3, &notifiers).await.is_err());
+
+    let digest = receiver.recv().await?;
+    assert_eq!(json!(3), digest["withinDays"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_sends_nothing_when_no_food_is_about_to_expire(pool: PgPool) -> Result<()> {
+    // Would fail to connect if it was used.
+    let notifiers: Vec<Box<dyn Notifier>> =
+        vec![Box::new(WebhookNotifier::new("http://127.0.0.1:9/hook")?)];
+    assert!(!notify_expiring_foods(&pool, 3, &notifiers).await?);
+
+    Ok(())
+}
+
+/// Creates foods that expired yesterday, expire in two days and in a month.
+async fn create_foods(pool: &PgPool) -> Result<Date> {
+    let app = init_app(pool.clone());
+    let today = OffsetDateTime::now_utc().date();
+    for (name, days) in [("Rice", 30), ("Eggs", 2), ("Milk", -1)] {
+        let mut req = Request::new(Method::Post, api_url("/foods"));
+        let date = today + Duration::days(days);
+        req.set_body(json!({"name": name, "bestBeforeDate": date.to_string()}));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Created, res.status());
+    }
+    Ok(today)
+}
+
+/// Sends the JSON bodies posted to the returned URL.
+fn webhook_stand_in() -> Result<(String, Receiver<Value>)> {
+    let (sender, receiver) = channel::unbounded();
+    let mut stand_in = tide::with_state(sender);
+    stand_in
+        .at("/hook")
+        .post(|mut req: tide::Request<Sender<Value>>| async move {
+            let body: Value = req.body_json().await?;
+            req.state().send(body).await?;
+            Ok(StatusCode::NoContent)
+        });
+    let listener = StdTcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/hook", listener.local_addr()?);
+    async_std::task::spawn(stand_in.listen(listener));
+
+    Ok((url, receiver))
+}
+
+/// Accepts a single email and sends what was written after `DATA`.
+async fn smtp_stand_in(listener: TcpListener, sender: Sender<String>) -> Result<()> {
+    let (mut stream, _) = listener.accept().await?;
+    let mut lines = BufReader::new(stream.clone()).lines();
+    stream.write_all(b"220 localhost\r\n").await?;
+
+    let mut data = String::new();
+    let mut in_data = false;
+    while let Some(line) = async_std::stream::StreamExt::next(&mut lines).await {
+        let line = line?;
+        if in_data {
+            if line == "." {
+                in_data = false;
+                stream.write_all(b"250 Queued\r\n").await?;
+            } else {
+                data.push_str(&line);
+                data.push('\n');
+            }
+            continue;
+        }
+
+        let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+        match command.as_str() {
+            "DATA" => {
+                in_data = true;
+                stream.write_all(b"354 Go ahead\r\n").await?;
+            }
+            "QUIT" => {
+                stream.write_all(b"221 Bye\r\n").await?;
+                break;
+            }
+            _ => stream.write_all(b"250 OK\r\n").await?,
+        }
+    }
+
+    sender.send(data).await?;
+    Ok(())
+}
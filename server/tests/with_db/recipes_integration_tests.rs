@@ -1,12 +1,13 @@
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{anyhow, Context, Result};
 use serde_json::{json, Value};
-use slice_n_dice_server::init_app;
+use slice_n_dice_server::{init_app, sanitize_recipes};
 use sqlx::PgPool;
 use tide::{
-    http::{Method, Request, Response, Url},
+    http::{Method, Request, Response},
     StatusCode,
 };
-use uuid::Uuid;
+
+use super::{api_url, assert_json_is_uuid, emap};
 
 #[sqlx::test]
 async fn it_returns_no_recipes_when_there_is_no_recipes(pool: PgPool) -> Result<()> {
@@ -102,21 +103,699 @@ async fn it_creates_and_retrieves_a_recipe(pool: PgPool) -> Result<()> {
     Ok(())
 }
 
-fn emap<T>(res: Result<T, tide::Error>) -> Result<T, anyhow::Error> {
-    res.map_err(tide::Error::into_inner)
+#[sqlx::test]
+async fn it_creates_and_retrieves_a_recipe_with_ingredients(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req_body = json!({
+        "name": "Pancakes",
+        "quick": true,
+        "body": "<p>Mix and fry</p>",
+        "ingredients": [
+            {"name": "flour", "quantity": 200.0, "unit": "g"},
+            {"name": "eggs", "quantity": 2.0},
+            {"name": "salt", "note": "a pinch"}
+        ]
+    });
+
+    // Create recipe
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(req_body.clone());
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let res_body = emap(res.body_string().await)?;
+    let res_body: Value = serde_json::from_str(&res_body)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?;
+
+    // Retrieve recipe
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body = emap(res.body_string().await)?;
+    let res_body: Value = serde_json::from_str(&res_body)?;
+
+    assert_eq!(
+        json!([
+            {"name": "flour", "quantity": 200.0, "unit": "g", "note": null},
+            {"name": "eggs", "quantity": 2.0, "unit": null, "note": null},
+            {"name": "salt", "quantity": null, "unit": null, "note": "a pinch"}
+        ]),
+        res_body["ingredients"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_replaces_ingredients_on_update(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    // Create recipe
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Pancakes",
+        "quick": true,
+        "body": "<p>Mix and fry</p>",
+        "ingredients": [{"name": "flour", "quantity": 200.0, "unit": "g"}]
+    }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?;
+
+    // Update recipe
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(json!({
+        "name": "Pancakes",
+        "quick": true,
+        "body": "<p>Mix and fry</p>",
+        "ingredients": [
+            {"name": "milk", "quantity": 300.0, "unit": "ml"},
+            {"name": "flour", "quantity": 250.0, "unit": "g"}
+        ]
+    }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    // Retrieve recipe
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+
+    let ingredients = res_body["ingredients"]
+        .as_array()
+        .ok_or(anyhow!("'.ingredients' is not an array"))?;
+    assert_eq!(2, ingredients.len());
+    assert_eq!("milk", ingredients[0]["name"]);
+    assert_eq!("flour", ingredients[1]["name"]);
+    assert_eq!(250.0, ingredients[1]["quantity"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_keeps_what_an_update_leaves_out(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Pancakes",
+        "quick": true,
+        "body": "<p>Mix and fry</p>",
+        "servings": 4,
+        "ingredients": [{"name": "flour", "quantity": 200.0, "unit": "g"}],
+        "tags": ["breakfast", "sweet"]
+    }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?;
+
+    // Saved the way the web client does
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(json!({
+        "id": uuid,
+        "name": "Crêpes",
+        "quick": true,
+        "body": "<p>Mix and fry thin</p>"
+    }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Crêpes", res_body["name"]);
+    assert_eq!(
+        json!([{"name": "flour", "quantity": 200.0, "unit": "g", "note": null}]),
+        res_body["ingredients"]
+    );
+    assert_eq!(json!(["breakfast", "sweet"]), res_body["tags"]);
+    assert_eq!(4, res_body["servings"]);
+
+    // Servings can still be cleared
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(json!({
+        "name": "Crêpes",
+        "quick": true,
+        "body": "<p>Mix and fry thin</p>",
+        "servings": null
+    }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(Value::Null, res_body["servings"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_filters_recipes_by_tags(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for (name, tags) in [
+        ("Salad", json!(["Vegetarian", "summer"])),
+        ("Soup", json!(["vegetarian"])),
+        ("Steak", json!([])),
+    ] {
+        let mut req = Request::new(Method::Post, api_url("/recipes"));
+        req.set_body(json!({"name": name, "quick": true, "body": "", "tags": tags}));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Created, res.status());
+    }
+
+    let req = Request::new(Method::Get, api_url("/recipes?tags=vegetarian"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Salad", res_body["recipes"][0]["name"]);
+    assert_eq!(
+        json!(["summer", "vegetarian"]),
+        res_body["recipes"][0]["tags"]
+    );
+    assert_eq!("Soup", res_body["recipes"][1]["name"]);
+    assert_eq!(Value::Null, res_body["recipes"][2]);
+
+    let req = Request::new(Method::Get, api_url("/recipes?tags=vegetarian,summer"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Salad", res_body["recipes"][0]["name"]);
+    assert_eq!(Value::Null, res_body["recipes"][1]);
+
+    let req = Request::new(Method::Get, api_url("/tags"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!([{"name": "summer", "count": 1}, {"name": "vegetarian", "count": 2}]),
+        res_body["tags"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_updates_recipe_tags(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(
+        json!({"name": "Salad", "quick": true, "body": "", "tags": ["summer", "vegetarian"]}),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?;
+
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(
+        json!({"name": "Salad", "quick": true, "body": "", "tags": ["vegetarian", "kid-friendly"]}),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!(["kid-friendly", "vegetarian"]), res_body["tags"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_searches_recipe_bodies_with_full_text_search(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    for (name, body) in [
+        (
+            "Moussaka",
+            "<p>Slice the aubergines and fry them in olive oil.</p>",
+        ),
+        ("Aubergine dip", "<p>Roast until soft then blend.</p>"),
+        ("Pancakes", "<p>Whisk the eggs with the flour.</p>"),
+    ] {
+        let mut req = Request::new(Method::Post, api_url("/recipes"));
+        req.set_body(json!({"name": name, "quick": false, "body": body}));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Created, res.status());
+    }
+
+    let req = Request::new(
+        Method::Get,
+        api_url("/recipes?search=aubergine&searchMode=fullText"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+
+    // Matches in the name rank above matches in the body
+    assert_eq!("Aubergine dip", res_body["recipes"][0]["name"]);
+    assert_eq!("Moussaka", res_body["recipes"][1]["name"]);
+    assert_eq!(Value::Null, res_body["recipes"][2]);
+
+    let snippet = res_body["recipes"][1]["snippet"]
+        .as_str()
+        .context("Should have a snippet")?;
+    assert!(snippet.contains("<mark>aubergines</mark>"));
+
+    // Name search doesn't return snippets
+    let req = Request::new(Method::Get, api_url("/recipes?search=moussaka"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Moussaka", res_body["recipes"][0]["name"]);
+    assert_eq!(Value::Null, res_body["recipes"][0]["snippet"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_imports_a_recipe_from_a_web_page(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes/import"));
+    req.set_body(include_str!("../data/recipe_json_ld.html"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_json_is_uuid(&res_body["id"])?;
+    assert_eq!("Mom's Tomato & Basil Soup", res_body["name"]);
+    assert_eq!(true, res_body["quick"]);
+    assert_eq!(
+        json!({"name": "olive oil", "quantity": 1.5, "unit": "tbsp", "note": null}),
+        res_body["ingredients"][0]
+    );
+
+    let mut req = Request::new(Method::Post, api_url("/recipes/import"));
+    req.set_body(include_str!("../data/example.html"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::UnprocessableEntity, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_sanitizes_recipe_bodies_on_write(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Toast",
+        "quick": true,
+        "body": r#"<p onclick="steal()">Toast <a href="javascript:steal()">bread</a></p><script>steal()</script>"#
+    }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let expected_body = r##"<p>Toast <a href="#">bread</a></p>"##;
+    assert_eq!(expected_body, res_body["body"]);
+
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?;
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(json!({
+        "name": "Toast",
+        "quick": true,
+        "body": r#"<p>Toast</p><img src="x" onerror="steal()">"#
+    }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(r#"<p>Toast</p><img src="x">"#, res_body["body"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_sanitizes_existing_recipe_bodies(pool: PgPool) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO recipes (name, quick, body_html, body_plain_text)
+         VALUES ('Clean', True, '<p>Clean</p>', 'Clean'),
+                ('Dirty', True, '<p>Dirty</p><script>steal()</script>', 'Dirty')",
+    )
+    .execute(&pool)
+    .await?;
+
+    assert_eq!(1, sanitize_recipes(&pool).await?);
+
+    let bodies: Vec<String> = sqlx::query_scalar("SELECT body_html FROM recipes ORDER BY name")
+        .fetch_all(&pool)
+        .await?;
+    assert_eq!(vec!["<p>Clean</p>", "<p>Dirty</p>"], bodies);
+
+    Ok(())
 }
 
-fn api_url(sub_url: &str) -> Url {
-    let mut url_string = String::from("https://localhost/api/v0");
-    url_string.push_str(sub_url);
-    Url::parse(&url_string).expect("Could not create url")
+#[sqlx::test]
+async fn it_scales_a_recipe_to_servings(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Omelette",
+        "quick": true,
+        "body": "",
+        "servings": 2,
+        "ingredients": [
+            {"name": "eggs", "quantity": 3},
+            {"name": "butter", "quantity": 15, "unit": "g"},
+            {"name": "salt"}
+        ]
+    }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(2, res_body["servings"]);
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?;
+
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}?servings=3", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(3, res_body["servings"]);
+    assert_eq!(5.0, res_body["ingredients"][0]["quantity"]);
+    assert_eq!(23.0, res_body["ingredients"][1]["quantity"]);
+    assert_eq!(Value::Null, res_body["ingredients"][2]["quantity"]);
+
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}?servings=3&units=imperial", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(0.75, res_body["ingredients"][1]["quantity"]);
+    assert_eq!("oz", res_body["ingredients"][1]["unit"]);
+
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}?servings=0", uuid)),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(json!({"name": "Omelette", "quick": true, "body": "", "servings": 0}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({"name": "Frittata", "quick": true, "body": "", "servings": -2}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_keeps_revisions_of_updated_recipes(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Pancakes",
+        "quick": true,
+        "body": "<p>Mix</p>\n<p>Fry</p>",
+        "ingredients": [{"name": "Flour", "quantity": 200.0, "unit": "g"}]
+    }));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?
+        .to_string();
+
+    let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+    req.set_body(json!({
+        "name": "Crêpes",
+        "quick": true,
+        "body": "<p>Mix</p>\n<p>Rest</p>\n<p>Fry</p>",
+        "ingredients": [{"name": "Flour", "quantity": 250.0, "unit": "g"}]
+    }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    // List revisions
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}/revisions", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(1, res_body["revisions"][0]["revision"]);
+    assert_eq!("Pancakes", res_body["revisions"][0]["name"]);
+    assert_eq!(Value::Null, res_body["revisions"][1]);
+
+    // Fetch one
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}/revisions/1", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("<p>Mix</p>\n<p>Fry</p>", res_body["body"]);
+    assert_eq!(200.0, res_body["ingredients"][0]["quantity"]);
+
+    // Diff with the current version
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}/diff?from=1", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!([
+            {"type": "unchanged", "text": "Mix"},
+            {"type": "added", "text": "Rest"},
+            {"type": "unchanged", "text": "Fry"}
+        ]),
+        res_body["lines"]
+    );
+
+    // Restore it, the replaced version becomes revision 2
+    let req = Request::new(
+        Method::Put,
+        api_url(&format!("/recipes/{}/revisions/1/restore", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Pancakes", res_body["name"]);
+    assert_eq!(200.0, res_body["ingredients"][0]["quantity"]);
+
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}/revisions/2", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Crêpes", res_body["name"]);
+
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}/revisions/3", uuid)),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    Ok(())
 }
 
-fn assert_json_is_uuid(v: &Value) -> Result<()> {
-    match v {
-        Value::String(s) => Uuid::parse_str(s)
-            .map(|_| ())
-            .context("Could not parse the json string into a UUID"),
-        _ => bail!("Json value should be a string to be a UUID"),
+#[sqlx::test]
+async fn it_numbers_revisions_of_concurrent_updates(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({"name": "Soup", "quick": true, "body": ""}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?
+        .to_string();
+
+    let updates: Vec<_> = (1..=5)
+        .map(|n| {
+            let app = app.clone();
+            let mut req = Request::new(Method::Put, api_url(&format!("/recipes/{}", uuid)));
+            req.set_body(json!({"name": format!("Soup {}", n), "quick": true, "body": ""}));
+            async_std::task::spawn(async move { app.respond::<_, Response>(req).await })
+        })
+        .collect();
+    for update in updates {
+        let res: Response = emap(update.await)?;
+        assert_eq!(StatusCode::Created, res.status());
+    }
+
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes/{}/revisions", uuid)),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let mut revisions: Vec<_> = res_body["revisions"]
+        .as_array()
+        .context("Should have revisions field that is an array")?
+        .iter()
+        .map(|r| r["revision"].clone())
+        .collect();
+    revisions.sort_by_key(|r| r.as_i64());
+    assert_eq!(json!([1, 2, 3, 4, 5]), Value::Array(revisions));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_archives_and_restores_recipes(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({"name": "Old Favourite", "quick": false, "body": "", "tags": ["retro"]}));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let uuid = res_body["id"]
+        .as_str()
+        .context("Should have id field that is a string")?
+        .to_string();
+    assert_eq!(Value::Null, res_body["archivedAt"]);
+
+    let req = Request::new(Method::Delete, api_url(&format!("/recipes/{}", uuid)));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    // Gone from listings and tags, still resolvable by id
+    let req = Request::new(Method::Get, api_url("/recipes"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!([]), res_body["recipes"]);
+
+    let req = Request::new(Method::Get, api_url("/tags"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(json!([]), res_body["tags"]);
+
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert!(res_body["archivedAt"].is_string());
+
+    let req = Request::new(Method::Get, api_url("/recipes?archived=true"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Old Favourite", res_body["recipes"][0]["name"]);
+
+    // Restore
+    let req = Request::new(Method::Put, api_url(&format!("/recipes/{}/restore", uuid)));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(Value::Null, res_body["archivedAt"]);
+
+    let req = Request::new(Method::Get, api_url("/recipes"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!("Old Favourite", res_body["recipes"][0]["name"]);
+
+    // Purge
+    let req = Request::new(
+        Method::Delete,
+        api_url(&format!("/recipes/{}?purge=true", uuid)),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Get, api_url(&format!("/recipes/{}", uuid)));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    let req = Request::new(Method::Put, api_url(&format!("/recipes/{}/restore", uuid)));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NotFound, res.status());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_pages_through_sorted_recipes(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let mut ids = Vec::new();
+    for name in ["Burrito", "Dal", "Curry", "Aubergine Bake"] {
+        let mut req = Request::new(Method::Post, api_url("/recipes"));
+        req.set_body(json!({"name": name, "quick": false, "body": ""}));
+        let mut res: Response = emap(app.respond(req).await)?;
+        let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+        ids.push(res_body["id"].clone());
     }
+
+    // Curry twice, Burrito once
+    for (date, id) in [
+        ("2022-01-01", &ids[2]),
+        ("2022-01-02", &ids[2]),
+        ("2022-01-03", &ids[0]),
+    ] {
+        let mut req = Request::new(Method::Put, api_url(&format!("/days/{}/lunch", date)));
+        req.set_body(json!({ "recipeId": id }));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+    }
+
+    for (sort, expected) in [
+        ("name", ["Aubergine Bake", "Burrito", "Curry", "Dal"]),
+        ("createdAt", ["Aubergine Bake", "Curry", "Dal", "Burrito"]),
+        ("lastCooked", ["Burrito", "Curry", "Aubergine Bake", "Dal"]),
+        ("timesCooked", ["Curry", "Burrito", "Aubergine Bake", "Dal"]),
+    ] {
+        let mut names = Vec::new();
+        let mut url = format!("/recipes?sort={}&limit=3", sort);
+        loop {
+            let req = Request::new(Method::Get, api_url(&url));
+            let mut res: Response = emap(app.respond(req).await)?;
+            assert_eq!(StatusCode::Ok, res.status());
+            let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+            assert_eq!(4, res_body["total"]);
+
+            for recipe in res_body["recipes"]
+                .as_array()
+                .context("'.recipes' is not an array")?
+            {
+                names.push(recipe["name"].as_str().unwrap_or_default().to_string());
+            }
+
+            match res_body["nextCursor"].as_str() {
+                Some(cursor) => url = format!("/recipes?sort={}&limit=3&cursor={}", sort, cursor),
+                None => break,
+            }
+        }
+        assert_eq!(expected.to_vec(), names, "sorted by {}", sort);
+    }
+
+    // A cursor only works with the sort it was made for
+    let req = Request::new(Method::Get, api_url("/recipes?limit=1"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    let cursor = res_body["nextCursor"]
+        .as_str()
+        .context("Should have a next cursor")?;
+    let req = Request::new(
+        Method::Get,
+        api_url(&format!("/recipes?sort=timesCooked&cursor={}", cursor)),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
 }
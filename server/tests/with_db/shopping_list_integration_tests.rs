@@ -0,0 +1,74 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use slice_n_dice_server::init_app;
+use sqlx::PgPool;
+use tide::{
+    http::{Method, Request, Response},
+    StatusCode,
+};
+
+use super::{api_url, emap};
+
+#[sqlx::test]
+async fn it_aggregates_ingredients_of_planned_meals(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    // Create recipe
+    let mut req = Request::new(Method::Post, api_url("/recipes"));
+    req.set_body(json!({
+        "name": "Pancakes",
+        "quick": true,
+        "body": "<p>Mix and fry</p>",
+        "ingredients": [
+            {"name": "Flour", "quantity": 200.0, "unit": "g"},
+            {"name": "Eggs", "quantity": 2.0}
+        ]
+    }));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    // Plan it for lunch and dinner, the only recipe is always picked
+    let req = Request::new(Method::Put, api_url("/days/2022-01-03/randomize"));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    // Add eggs to the fridge
+    let mut req = Request::new(Method::Post, api_url("/foods"));
+    req.set_body(json!({"name": " eggs ", "bestBeforeDate": "2022-01-10"}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Created, res.status());
+
+    // Retrieve shopping list
+    let req = Request::new(
+        Method::Get,
+        api_url("/shopping-list?from=2022-01-01&to=2022-01-07"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+
+    assert_eq!(
+        json!([
+            {"name": "Eggs", "quantity": 4.0, "unit": null, "inFridge": true},
+            {"name": "Flour", "quantity": 400.0, "unit": "g", "inFridge": false}
+        ]),
+        res_body["items"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_rejects_an_inverted_date_range(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req = Request::new(
+        Method::Get,
+        api_url("/shopping-list?from=2022-01-07&to=2022-01-01"),
+    );
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::BadRequest, res.status());
+
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use slice_n_dice_server::init_app;
+use sqlx::PgPool;
+use tide::{
+    http::{Method, Request, Response},
+    StatusCode,
+};
+
+use super::{api_url, create_food, create_recipe, emap};
+
+#[sqlx::test]
+async fn it_returns_stats_for_a_range(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let toast = create_recipe(&app, "Toast", true).await?;
+    let stew = create_recipe(&app, "Stew", false).await?;
+    create_recipe(&app, "Salad", true).await?;
+    let old = create_recipe(&app, "Old", false).await?;
+
+    for (date, slot, recipe_id) in [
+        ("2022-01-03", "lunch", &toast),
+        ("2022-01-03", "dinner", &stew),
+        ("2022-01-04", "lunch", &toast),
+        ("2021-12-31", "lunch", &stew),
+    ] {
+        let mut req = Request::new(Method::Put, api_url(&format!("/days/{}/{}", date, slot)));
+        req.set_body(json!({ "recipeId": recipe_id }));
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+    }
+    for (date, slot) in [
+        ("2022-01-04", "dinner"),
+        ("2022-01-10", "lunch"),
+        ("2022-02-01", "dinner"),
+    ] {
+        let req = Request::new(
+            Method::Put,
+            api_url(&format!("/days/{}/{}/cheat", date, slot)),
+        );
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::Ok, res.status());
+    }
+
+    let req = Request::new(Method::Delete, api_url(&format!("/recipes/{}", old)));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let req = Request::new(Method::Get, api_url("/stats?from=2022-01-01&to=2022-02-01"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+
+    assert_eq!(
+        json!([
+            {"id": toast, "name": "Toast", "quick": true, "timesPlanned": 2, "lastPlanned": "2022-01-04"},
+            {"id": stew, "name": "Stew", "quick": false, "timesPlanned": 1, "lastPlanned": "2022-01-03"}
+        ]),
+        res_body["recipes"]
+    );
+    assert_eq!(json!(["Salad"]), names(&res_body["neverPlanned"])?);
+    assert_eq!(3, res_body["cheatMeals"]["total"]);
+    assert_eq!(
+        json!([
+            {"start": "2021-12-27", "count": 0},
+            {"start": "2022-01-03", "count": 1},
+            {"start": "2022-01-10", "count": 1},
+            {"start": "2022-01-17", "count": 0},
+            {"start": "2022-01-24", "count": 0},
+            {"start": "2022-01-31", "count": 1}
+        ]),
+        res_body["cheatMeals"]["byWeek"]
+    );
+    assert_eq!(
+        json!([
+            {"start": "2022-01-01", "count": 2},
+            {"start": "2022-02-01", "count": 1}
+        ]),
+        res_body["cheatMeals"]["byMonth"]
+    );
+    assert_eq!(2, res_body["quickMeals"]["quick"]);
+    assert_eq!(1, res_body["quickMeals"]["notQuick"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn it_returns_empty_stats_when_nothing_was_planned(pool: PgPool) -> Result<()> {
+    let app = init_app(pool);
+
+    let req = Request::new(Method::Get, api_url("/stats?from=2022-01-01&to=2022-01-01"));
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+
+    assert_eq!(json!([]), res_body["recipes"]);
+    assert_eq!(Value::Null, res_body["quickMeals"]["quickRatio"]);
+    assert_eq!(
+        json!([{"start": "2021-12-27", "count": 0}]),
+        res_body["cheatMeals"]["byWeek"]
+    );
+
+    Ok(())
+}
+
+fn names(recipes: &Value) -> Result<Value> {
+    Ok(recipes
+        .as_array()
+        .ok_or(anyhow!("Recipes are not an array"))?
+        .iter()
+        .map(|r| r["name"].clone())
+        .collect())
+}
+
+#[sqlx::test]
+async fn it_reports_what_became_of_foods(pool: PgPool) -> Result<()> {
+    let app = init_app(pool.clone());
+
+    for (name, date, reason) in [
+        ("Milk", "2022-01-20", "thrownAway"),
+        ("Bread", "2022-02-01", "thrownAway"),
+        ("Bread", "2022-02-05", "thrownAway"),
+        ("Cheese", "2022-03-01", "givenAway"),
+        ("Apple", "2022-02-12", "consumed"),
+    ] {
+        let id = create_food(&app, json!({"name": name, "bestBeforeDate": date})).await?;
+        let req = Request::new(
+            Method::Delete,
+            api_url(&format!("/foods/{}?reason={}", id, reason)),
+        );
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::NoContent, res.status());
+    }
+
+    // A reason has to be given
+    let typo = create_food(
+        &app,
+        json!({"name": "Typo", "bestBeforeDate": "2022-02-12"}),
+    )
+    .await?;
+    for query in ["", "?reason=thrown_away"] {
+        let req = Request::new(
+            Method::Delete,
+            api_url(&format!("/foods/{}{}", typo, query)),
+        );
+        let res: Response = emap(app.respond(req).await)?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+    }
+
+    let eggs = create_food(
+        &app,
+        json!({"name": "Eggs", "bestBeforeDate": "2022-02-14", "quantity": 6}),
+    )
+    .await?;
+    let mut req = Request::new(Method::Post, api_url(&format!("/foods/{}/consume", eggs)));
+    req.set_body(json!({"quantity": 2}));
+    let res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+
+    sqlx::query(
+        "UPDATE food_events
+         SET happened_on = CASE name WHEN 'Milk' THEN '2022-01-15'::date ELSE '2022-02-10' END",
+    )
+    .execute(&pool)
+    .await?;
+
+    let req = Request::new(
+        Method::Get,
+        api_url("/stats/foods?from=2022-01-01&to=2022-02-28"),
+    );
+    let mut res: Response = emap(app.respond(req).await)?;
+    assert_eq!(StatusCode::Ok, res.status());
+    let res_body: Value = serde_json::from_str(&emap(res.body_string().await)?)?;
+    assert_eq!(
+        json!({
+            "from": "2022-01-01",
+            "to": "2022-02-28",
+            "consumed": 2,
+            "thrownAway": 3,
+            "givenAway": 1,
+            "wasteByMonth": [
+                {"start": "2022-01-01", "count": 1},
+                {"start": "2022-02-01", "count": 2}
+            ],
+            "mostWasted": [
+                {"name": "Bread", "timesThrownAway": 2},
+                {"name": "Milk", "timesThrownAway": 1}
+            ],
+            "averageDaysBeforeExpiry": 3.0
+        }),
+        res_body
+    );
+
+    let eggs_eaten: Option<f64> =
+        sqlx::query_scalar("SELECT quantity FROM food_events WHERE name = 'Eggs'")
+            .fetch_one(&pool)
+            .await?;
+    assert_eq!(Some(2.0), eggs_eaten);
+
+    Ok(())
+}